@@ -0,0 +1,170 @@
+//! Unsigned integers too big for a u64, e.g. counting day 10 adapter arrangements for long chains
+//! Only supports what the puzzles need, rather than being a general purpose big integer library
+
+use std::cmp::Ordering;
+use std::ops::{Add, AddAssign};
+
+/// An unsigned integer of any size
+/// Stored as base 2^32 digits, least significant first, without any leading zero digits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// the value as a u64, or None if it is too big
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.digits[..] {
+            [] => Some(0),
+            [low] => Some(low as u64),
+            [low, high] => Some((high as u64) << 32 | low as u64),
+            _ => None,
+        }
+    }
+
+    /// divides in place, returning the remainder
+    fn div_rem_u32(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for digit in self.digits.iter_mut().rev() {
+            let value = remainder << 32 | *digit as u64;
+            *digit = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        self.normalize();
+        remainder as u32
+    }
+
+    fn normalize(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        let mut number = BigUint {
+            digits: vec![value as u32, (value >> 32) as u32],
+        };
+        number.normalize();
+        number
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.digits.len() < other.digits.len() {
+            self.digits.resize(other.digits.len(), 0);
+        }
+        let mut carry = 0u64;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            let sum = *digit as u64 + other.digits.get(i).copied().unwrap_or(0) as u64 + carry;
+            *digit = sum as u32;
+            carry = sum >> 32;
+            if carry == 0 && i >= other.digits.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.digits.push(carry as u32);
+        }
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut sum = self.clone();
+        sum += other;
+        sum
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // no leading zeros, so more digits is always bigger
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for BigUint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // split into base 10^9 chunks, least significant first
+        const CHUNK: u32 = 1_000_000_000;
+        let mut number = self.clone();
+        let mut chunks = Vec::new();
+        while !number.is_zero() {
+            chunks.push(number.div_rem_u32(CHUNK));
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn add_works() {
+        let mut number = BigUint::from(u64::MAX);
+        number += &BigUint::from(1);
+        assert_eq!(None, number.to_u64());
+        assert_eq!("18446744073709551616", number.to_string());
+
+        let number = &number + &number;
+        assert_eq!("36893488147419103232", number.to_string());
+        assert_eq!(Some(3), (&BigUint::from(1) + &BigUint::from(2)).to_u64());
+        assert_eq!(Some(7), (&BigUint::zero() + &BigUint::from(7)).to_u64());
+    }
+
+    #[test]
+    fn display_works() {
+        assert_eq!("0", BigUint::zero().to_string());
+        assert_eq!("1000000000", BigUint::from(1_000_000_000).to_string());
+        assert_eq!(u64::MAX.to_string(), BigUint::from(u64::MAX).to_string());
+
+        // 2^128 - 1, built by doubling and adding one
+        let mut number = BigUint::zero();
+        for _ in 0..128 {
+            number = &(&number + &number) + &BigUint::from(1);
+        }
+        assert_eq!(u128::MAX.to_string(), number.to_string());
+    }
+
+    #[test]
+    fn ordering_works() {
+        let small = BigUint::from(5);
+        let large = &BigUint::from(u64::MAX) + &small;
+        assert!(small < large);
+        assert!(BigUint::from(u64::MAX) < large);
+        assert!(BigUint::zero() < small);
+        assert_eq!(Ordering::Equal, large.cmp(&large.clone()));
+    }
+}
@@ -0,0 +1,147 @@
+//! Optional settings at the top of an input, for puzzles where the examples use different parameters to the real input
+//! e.g. day 9's examples use a preamble of 5 rather than 25, which an input can set with:
+//! ```text
+//! # preamble = 5
+//! ```
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The settings from the header of an input, see [`split_header`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config<'a> {
+    /// the line number (starting at 1) each setting was on, and its value
+    settings: HashMap<&'a str, (usize, &'a str)>,
+    /// how many lines the header took up
+    pub lines: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// a header line (starting at 1) that isn't `# key = value`
+    Malformed(usize),
+    /// the same setting more than once
+    Duplicate { line: usize, key: String },
+    /// a value that can't be parsed as the type the setting needs
+    InvalidValue {
+        line: usize,
+        key: String,
+        value: String,
+    },
+}
+
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Malformed(line) => {
+                write!(f, "line {}: expected a setting like `# key = value`", line)
+            }
+            ConfigError::Duplicate { line, key } => {
+                write!(f, "line {}: {} has already been set", line, key)
+            }
+            ConfigError::InvalidValue { line, key, value } => {
+                write!(f, "line {}: {} is not a valid {}", line, value, key)
+            }
+        }
+    }
+}
+
+/// Splits any `# key = value` lines off the start of input, returning their settings and the rest of the input
+/// Inputs without a header have no settings, so the defaults are used
+pub fn split_header(input: &str) -> Result<(Config<'_>, &str), ConfigError> {
+    let mut config = Config::default();
+    let mut rest = input;
+    while let Some(line) = rest.strip_prefix('#') {
+        let (line, remainder) = match line.find('\n') {
+            Some(end) => (&line[..end], &line[end + 1..]),
+            None => (line, ""),
+        };
+        rest = remainder;
+        config.lines += 1;
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(ConfigError::Malformed(config.lines))?;
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            return Err(ConfigError::Malformed(config.lines));
+        }
+        if config.settings.insert(key, (config.lines, value)).is_some() {
+            return Err(ConfigError::Duplicate {
+                line: config.lines,
+                key: key.to_string(),
+            });
+        }
+    }
+    Ok((config, rest))
+}
+
+impl<'a> Config<'a> {
+    /// the value of a setting, or None if it isn't set
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>, ConfigError> {
+        match self.settings.get(key) {
+            None => Ok(None),
+            Some(&(line, value)) => {
+                value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| ConfigError::InvalidValue {
+                        line,
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_header_works() {
+        let (config, rest) = split_header("# preamble = 5\r\n#name=test\n1\n2\n").unwrap();
+        assert_eq!(2, config.lines);
+        assert_eq!(Some(5), config.get::<usize>("preamble").unwrap());
+        assert_eq!(Some("test".to_string()), config.get("name").unwrap());
+        assert_eq!(None, config.get::<usize>("missing").unwrap());
+        assert_eq!("1\n2\n", rest);
+
+        let (config, rest) = split_header("1\n# not = header\n").unwrap();
+        assert_eq!(0, config.lines);
+        assert_eq!("1\n# not = header\n", rest);
+
+        let (config, rest) = split_header("# only = header").unwrap();
+        assert_eq!(1, config.lines);
+        assert_eq!("", rest);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(ConfigError::Malformed(2)),
+            split_header("# a = 1\n# b\n1")
+        );
+        assert_eq!(Err(ConfigError::Malformed(1)), split_header("# = 1\n1"));
+        assert_eq!(
+            Err(ConfigError::Duplicate {
+                line: 2,
+                key: "a".to_string()
+            }),
+            split_header("# a = 1\n# a = 2\n1")
+        );
+
+        let (config, _) = split_header("# preamble = five\n1").unwrap();
+        assert_eq!(
+            Err(ConfigError::InvalidValue {
+                line: 1,
+                key: "preamble".to_string(),
+                value: "five".to_string()
+            }),
+            config.get::<usize>("preamble")
+        );
+    }
+}
@@ -0,0 +1,728 @@
+//! The handheld game console from day 8, as a small virtual machine
+//!
+//! Instructions are looked up by mnemonic in an InstructionSet, so new instructions can be added by registering
+//! another Opcode without changing the Machine that runs them.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use fixedbitset::FixedBitSet;
+
+pub mod analysis;
+pub mod assembler;
+pub mod debugger;
+pub mod trace;
+
+use trace::Step;
+
+/// One of the machine's 26 registers, named `a` to `z`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Register(u8);
+
+/// The accumulator used by the handheld's `acc` instruction is register `a`
+pub const ACCUMULATOR: Register = Register(0);
+
+const REGISTER_COUNT: usize = 26;
+
+impl Register {
+    /// returns None unless name is a lowercase letter
+    pub fn new(name: char) -> Option<Self> {
+        match name {
+            'a'..='z' => Some(Register(name as u8 - b'a')),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", (b'a' + self.0) as char)
+    }
+}
+
+/// The values stored in every register, all starting at 0
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Registers([i64; REGISTER_COUNT]);
+
+impl Registers {
+    pub fn get(&self, register: Register) -> i64 {
+        self.0[register.0 as usize]
+    }
+
+    pub fn set(&mut self, register: Register, value: i64) {
+        self.0[register.0 as usize] = value;
+    }
+
+    /// the value of an operand, reading the register if necessary
+    pub fn value(&self, operand: Operand) -> i64 {
+        match operand {
+            Operand::Value(x) => x,
+            Operand::Register(r) => self.get(r),
+        }
+    }
+}
+
+/// An argument to an instruction, either a number like `+4` or a register like `b`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Value(i64),
+    Register(Register),
+}
+
+impl FromStr for Operand {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(name), None) = (chars.next(), chars.next()) {
+            if let Some(register) = Register::new(name) {
+                return Ok(Operand::Register(register));
+            }
+        }
+        s.parse::<i64>()
+            .map(Operand::Value)
+            .map_err(|_| ParseError::InvalidOperand(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Value(x) => write!(f, "{:+}", x),
+            Operand::Register(r) => write!(f, "{}", r),
+        }
+    }
+}
+
+/// Where the instruction pointer goes after an instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    /// on to the next instruction
+    Next,
+    /// jump relative to this instruction by the value of the first operand
+    Jump,
+}
+
+/// Why the machine stopped part way through an instruction
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// an instruction's arithmetic overflowed an i64
+    Overflow,
+    /// a jump to somewhere other than an instruction or the end of the program
+    OutOfBounds { target: i64 },
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Fault::Overflow => write!(f, "arithmetic overflow"),
+            Fault::OutOfBounds { target } => write!(f, "jump out of bounds to {}", target),
+        }
+    }
+}
+
+/// The definition of an instruction: its mnemonic, what it does to the registers and where it goes next
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    /// how many operands the instruction takes
+    pub arity: usize,
+    /// changes the registers, given the instruction's operands
+    pub execute: fn(&mut Registers, &[Operand]) -> Result<(), Fault>,
+    pub control: Control,
+}
+
+impl std::fmt::Debug for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Opcode({})", self.mnemonic)
+    }
+}
+
+/// Each opcode is defined exactly once as a static, so they are compared by address
+impl PartialEq for Opcode {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// `acc +x` adds x to the accumulator
+pub static ACC: Opcode = Opcode {
+    mnemonic: "acc",
+    arity: 1,
+    execute: |registers, operands| {
+        let value = registers
+            .get(ACCUMULATOR)
+            .checked_add(registers.value(operands[0]))
+            .ok_or(Fault::Overflow)?;
+        registers.set(ACCUMULATOR, value);
+        Ok(())
+    },
+    control: Control::Next,
+};
+
+/// `jmp +x` jumps x instructions away from itself
+pub static JMP: Opcode = Opcode {
+    mnemonic: "jmp",
+    arity: 1,
+    execute: |_, _| Ok(()),
+    control: Control::Jump,
+};
+
+/// `nop +x` does nothing, its operand is ignored
+pub static NOP: Opcode = Opcode {
+    mnemonic: "nop",
+    arity: 1,
+    execute: |_, _| Ok(()),
+    control: Control::Next,
+};
+
+/// The opcodes a Program may use, looked up by mnemonic when parsing
+#[derive(Clone, Debug, Default)]
+pub struct InstructionSet {
+    opcodes: HashMap<&'static str, &'static Opcode>,
+}
+
+impl InstructionSet {
+    /// an instruction set with no instructions at all
+    pub fn new() -> Self {
+        InstructionSet::default()
+    }
+
+    /// the instructions understood by the handheld: acc, jmp and nop
+    pub fn handheld() -> Self {
+        let mut set = InstructionSet::new();
+        set.register(&ACC);
+        set.register(&JMP);
+        set.register(&NOP);
+        set
+    }
+
+    /// Adds an opcode, replacing any opcode that already had the same mnemonic
+    pub fn register(&mut self, opcode: &'static Opcode) {
+        self.opcodes.insert(opcode.mnemonic, opcode);
+    }
+
+    pub fn get(&self, mnemonic: &str) -> Option<&'static Opcode> {
+        self.opcodes.get(mnemonic).cloned()
+    }
+
+    /// parses a single instruction like `acc +1` or `cpy a, b`
+    pub fn parse_instruction(&self, s: &str) -> Result<Instruction, ParseError> {
+        let s = s.trim();
+        let mut iter = s.splitn(2, ' ');
+        let mnemonic = iter.next().unwrap_or_default();
+        let opcode = self
+            .get(mnemonic)
+            .ok_or_else(|| ParseError::UnknownOpcode(mnemonic.to_string()))?;
+        let operands = match iter.next() {
+            Some(operands) => operands
+                .split(',')
+                .map(|operand| operand.trim().parse())
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        Instruction::new(opcode, operands)
+    }
+
+    /// parses one instruction per line
+    pub fn parse_program(&self, s: &str) -> Result<Program, ProgramParseError> {
+        s.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                self.parse_instruction(line)
+                    .map_err(|error| ProgramParseError { line: i + 1, error })
+            })
+            .collect::<Result<_, _>>()
+            .map(|instructions| Program { instructions })
+    }
+}
+
+/// Why an instruction could not be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    UnknownOpcode(String),
+    InvalidOperand(String),
+    WrongArity {
+        mnemonic: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownOpcode(s) => write!(f, "unknown instruction: {:?}", s),
+            ParseError::InvalidOperand(s) => write!(f, "invalid operand: {:?}", s),
+            ParseError::WrongArity {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} operand(s) but was given {}",
+                mnemonic, expected, found
+            ),
+        }
+    }
+}
+
+/// A ParseError and the line of the program (starting at 1) it happened on
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramParseError {
+    pub line: usize,
+    pub error: ParseError,
+}
+
+impl std::error::Error for ProgramParseError {}
+
+impl std::fmt::Display for ProgramParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+/// An opcode and its operands, which always number the opcode's arity
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    opcode: &'static Opcode,
+    operands: Vec<Operand>,
+}
+
+impl Instruction {
+    pub fn new(opcode: &'static Opcode, operands: Vec<Operand>) -> Result<Self, ParseError> {
+        if operands.len() != opcode.arity {
+            return Err(ParseError::WrongArity {
+                mnemonic: opcode.mnemonic,
+                expected: opcode.arity,
+                found: operands.len(),
+            });
+        }
+        Ok(Instruction { opcode, operands })
+    }
+
+    pub fn opcode(&self) -> &'static Opcode {
+        self.opcode
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic)?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of instructions, indexed from 0
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Program { instructions }
+    }
+
+    pub fn get(&self, address: usize) -> Option<&Instruction> {
+        self.instructions.get(address)
+    }
+
+    /// replaces the instruction at address, returning the old one
+    /// panics if address is not within the program
+    pub fn replace(&mut self, address: usize, instruction: Instruction) -> Instruction {
+        std::mem::replace(&mut self.instructions[address], instruction)
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+}
+
+/// Disassembles the program, one instruction per line in the same format as the puzzle input
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the machine can keep running, and if not why it stopped
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Running,
+    /// the instruction pointer moved to just after the last instruction, which is how programs finish
+    Halted,
+    /// the instruction at the instruction pointer has already been run, so the program would loop forever
+    Looped,
+    /// the instruction at address could not be run
+    Fault {
+        address: usize,
+        fault: Fault,
+    },
+}
+
+/// Runs a Program one instruction at a time
+/// visited records every unique instruction_pointer (i.e. Program index) that has already been executed
+/// in order to detect infinite loops at runtime, it grows to fit the program and is reused after a reset
+/// trace is only recorded when asked for, see Machine::record_trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct Machine {
+    instruction_pointer: usize,
+    registers: Registers,
+    visited: FixedBitSet,
+    status: Status,
+    trace: Option<Vec<Step>>,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Machine {
+            instruction_pointer: 0,
+            registers: Registers::default(),
+            visited: FixedBitSet::with_capacity(0),
+            status: Status::Running,
+            trace: None,
+        }
+    }
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine::default()
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn accumulator(&self) -> i64 {
+        self.registers.get(ACCUMULATOR)
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// From now on, record every instruction the machine runs
+    /// the trace is cleared on reset, but recording carries on
+    pub fn record_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// every instruction run so far, if recording
+    pub fn trace(&self) -> Option<&[Step]> {
+        self.trace.as_deref()
+    }
+
+    /// puts the machine back how it started, ready to run a program from the beginning
+    /// keeps hold of any memory already allocated, so that running many programs doesn't keep reallocating
+    pub fn reset(&mut self) {
+        self.instruction_pointer = 0;
+        self.registers = Registers::default();
+        self.visited.clear();
+        self.status = Status::Running;
+        if let Some(trace) = &mut self.trace {
+            trace.clear();
+        }
+    }
+
+    /// Step through one instruction of the program at the instruction_pointer
+    /// returns Status::Running if the machine can keep going, otherwise the reason it stopped
+    /// once the machine has stopped, stepping makes no further changes
+    pub fn step(&mut self, program: &Program) -> &Status {
+        if self.status != Status::Running {
+            return &self.status;
+        }
+
+        self.visited.grow(program.len());
+        self.status = match program.get(self.instruction_pointer) {
+            None => Status::Halted,
+            Some(_) if self.visited.put(self.instruction_pointer) => {
+                // instruction has been run before...
+                // infinite loop detected - stop
+                Status::Looped
+            }
+            Some(instruction) => match self.execute(instruction, program.len()) {
+                Ok(()) => Status::Running,
+                Err(fault) => Status::Fault {
+                    address: self.instruction_pointer,
+                    fault,
+                },
+            },
+        };
+        &self.status
+    }
+
+    /// resets the machine and runs through the program until it stops
+    pub fn run(&mut self, program: &Program) -> &Status {
+        self.reset();
+        while self.step(program) == &Status::Running {}
+        &self.status
+    }
+
+    /// Mutates self in order to do the instruction, moving the instruction_pointer on afterwards
+    /// len is the length of the program, jumping further than just past its end is a fault
+    /// an instruction that faults is still recorded in the trace, but the instruction_pointer stays on it
+    fn execute(&mut self, instruction: &Instruction, len: usize) -> Result<(), Fault> {
+        let ip = self.instruction_pointer;
+        let acc_before = self.accumulator();
+        let opcode = instruction.opcode;
+        let next =
+            (opcode.execute)(&mut self.registers, &instruction.operands).and_then(
+                |()| match opcode.control {
+                    Control::Next => Ok(ip + 1),
+                    Control::Jump => {
+                        let offset = self.registers.value(instruction.operands[0]);
+                        let target = (ip as i64).checked_add(offset).ok_or(Fault::Overflow)?;
+                        if target < 0 || target as usize > len {
+                            return Err(Fault::OutOfBounds { target });
+                        }
+                        Ok(target as usize)
+                    }
+                },
+            );
+
+        if let Some(trace) = &mut self.trace {
+            trace.push(Step {
+                ip,
+                instruction: instruction.clone(),
+                acc_before,
+                acc_after: self.registers.get(ACCUMULATOR),
+            });
+        }
+        self.instruction_pointer = next?;
+        Ok(())
+    }
+}
+
+/// The example program from day 8, for tests throughout the console
+#[cfg(test)]
+pub(crate) const TEST_PROGRAM: &str = "\
+nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+#[cfg(test)]
+pub(crate) fn test_program() -> Program {
+    InstructionSet::handheld()
+        .parse_program(TEST_PROGRAM)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_works() {
+        let set = InstructionSet::handheld();
+        assert_eq!(
+            Instruction::new(&JMP, vec![Operand::Value(-3)]).unwrap(),
+            set.parse_instruction("jmp -3").unwrap()
+        );
+        assert_eq!(
+            Instruction::new(&ACC, vec![Operand::Register(Register::new('c').unwrap())]).unwrap(),
+            set.parse_instruction("acc c").unwrap()
+        );
+        assert_eq!(
+            Err(ParseError::UnknownOpcode("hcf".to_string())),
+            set.parse_instruction("hcf +0")
+        );
+        assert_eq!(
+            Err(ParseError::InvalidOperand("+x".to_string())),
+            set.parse_instruction("acc +x")
+        );
+        assert_eq!(
+            Err(ParseError::WrongArity {
+                mnemonic: "nop",
+                expected: 1,
+                found: 2
+            }),
+            set.parse_instruction("nop +1, +2")
+        );
+        // instructions built directly are checked the same way
+        assert_eq!(
+            Err(ParseError::WrongArity {
+                mnemonic: "jmp",
+                expected: 1,
+                found: 0
+            }),
+            Instruction::new(&JMP, vec![])
+        );
+        assert_eq!(
+            "line 2: unknown instruction: \"hcf\"",
+            set.parse_program("nop +0\nhcf +0").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn instruction_display() {
+        let set = InstructionSet::handheld();
+        for line in TEST_PROGRAM.lines() {
+            assert_eq!(line, set.parse_instruction(line).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn machine_loops() {
+        let program = test_program();
+        let mut machine = Machine::new();
+
+        assert_eq!(&Status::Looped, machine.run(&program));
+        assert_eq!(5, machine.accumulator());
+        assert_eq!(1, machine.instruction_pointer());
+
+        // a stopped machine stays stopped
+        assert_eq!(&Status::Looped, machine.step(&program));
+    }
+
+    #[test]
+    fn reset_works() {
+        let program = test_program();
+        let mut machine = Machine::new();
+        machine.record_trace();
+        machine.run(&program);
+        let first_run = machine.clone();
+
+        machine.reset();
+        assert_eq!(0, machine.instruction_pointer());
+        assert_eq!(0, machine.accumulator());
+        assert_eq!(&Status::Running, machine.status());
+        assert_eq!(Some(&[][..]), machine.trace());
+        assert_eq!(0, machine.visited.count_ones(..));
+        assert_eq!(program.len(), machine.visited.len());
+
+        machine.run(&program);
+        assert_eq!(first_run, machine);
+
+        // a longer program grows the visited set
+        let longer = InstructionSet::handheld()
+            .parse_program(&format!("{}\nnop +0\nnop +0", TEST_PROGRAM))
+            .unwrap();
+        assert_eq!(&Status::Looped, machine.run(&longer));
+        assert_eq!(longer.len(), machine.visited.len());
+    }
+
+    #[test]
+    fn machine_halts() {
+        let program = InstructionSet::handheld()
+            .parse_program("acc +2\njmp +2\nacc +100\nacc -1")
+            .unwrap();
+        let mut machine = Machine::new();
+
+        assert_eq!(&Status::Running, machine.step(&program));
+        assert_eq!(2, machine.accumulator());
+        assert_eq!(&Status::Halted, machine.run(&program));
+        assert_eq!(1, machine.accumulator());
+    }
+
+    #[test]
+    fn machine_faults() {
+        let program = InstructionSet::handheld()
+            .parse_program(&format!("acc +1\nacc {}", i64::MAX))
+            .unwrap();
+        let mut machine = Machine::new();
+
+        assert_eq!(
+            &Status::Fault {
+                address: 1,
+                fault: Fault::Overflow
+            },
+            machine.run(&program)
+        );
+    }
+
+    #[test]
+    fn jumps_out_of_bounds() {
+        let set = InstructionSet::handheld();
+        let mut machine = Machine::new();
+
+        // jumping to just after the last instruction is the same as running off the end
+        let program = set.parse_program("jmp +2\nacc +1").unwrap();
+        assert_eq!(&Status::Halted, machine.run(&program));
+
+        let program = set.parse_program("acc +1\njmp -2").unwrap();
+        assert_eq!(
+            &Status::Fault {
+                address: 1,
+                fault: Fault::OutOfBounds { target: -1 }
+            },
+            machine.run(&program)
+        );
+        assert_eq!(1, machine.instruction_pointer());
+        assert_eq!(1, machine.accumulator());
+
+        let program = set.parse_program("nop +0\njmp +2").unwrap();
+        assert_eq!(
+            &Status::Fault {
+                address: 1,
+                fault: Fault::OutOfBounds { target: 3 }
+            },
+            machine.run(&program)
+        );
+        assert_eq!(
+            "jump out of bounds to 3",
+            Fault::OutOfBounds { target: 3 }.to_string()
+        );
+    }
+
+    static CPY: Opcode = Opcode {
+        mnemonic: "cpy",
+        arity: 2,
+        execute: |registers, operands| match operands[1] {
+            Operand::Register(target) => {
+                registers.set(target, registers.value(operands[0]));
+                Ok(())
+            }
+            // copying into a number doesn't do anything
+            Operand::Value(_) => Ok(()),
+        },
+        control: Control::Next,
+    };
+
+    #[test]
+    fn new_instructions() {
+        let mut set = InstructionSet::handheld();
+        set.register(&CPY);
+
+        let program = set
+            .parse_program("cpy +5, b\nacc b\nacc b\ncpy a, c\ncpy +1, d\njmp d")
+            .unwrap();
+        let mut machine = Machine::new();
+
+        assert_eq!(&Status::Halted, machine.run(&program));
+        assert_eq!(10, machine.accumulator());
+        assert_eq!(5, machine.registers().get(Register::new('b').unwrap()));
+        assert_eq!(10, machine.registers().get(Register::new('c').unwrap()));
+    }
+}
@@ -0,0 +1,275 @@
+//! Static control flow analysis of a Program, answering questions about it without running a Machine
+//!
+//! Only control flow is considered: an instruction whose arithmetic overflows when it is run isn't predicted.
+
+use super::{Control, Instruction, Operand, Program};
+
+/// Where the program goes after running an instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    /// on to the instruction at this address
+    To(usize),
+    /// to just after the last instruction, so it halts
+    Halt,
+    /// out of bounds, which is a fault
+    Fault,
+    /// a jump by the value of a register, which can't be known without running the program
+    Unknown,
+}
+
+/// Where the program goes after running instruction, which is at address in a program of length len
+pub fn flow(instruction: &Instruction, address: usize, len: usize) -> Flow {
+    let target = match instruction.opcode().control {
+        Control::Next => Some(address as i64 + 1),
+        Control::Jump => match instruction.operands()[0] {
+            Operand::Value(offset) => (address as i64).checked_add(offset),
+            Operand::Register(_) => return Flow::Unknown,
+        },
+    };
+    match target {
+        Some(target) if target >= 0 && (target as usize) < len => Flow::To(target as usize),
+        Some(target) if target as usize == len => Flow::Halt,
+        _ => Flow::Fault,
+    }
+}
+
+fn flows(program: &Program) -> Vec<Flow> {
+    program
+        .instructions()
+        .iter()
+        .enumerate()
+        .map(|(address, instruction)| flow(instruction, address, program.len()))
+        .collect()
+}
+
+/// A run of instructions that are always run together: control only enters at the first one
+/// and only leaves from the last one
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// the address just after the last instruction in the block
+    pub end: usize,
+    /// where the last instruction goes
+    pub exit: Flow,
+}
+
+/// Splits the program into basic blocks, in address order
+pub fn basic_blocks(program: &Program) -> Vec<BasicBlock> {
+    let flows = flows(program);
+
+    // a block starts at the start of the program, at every jump target and after every jump
+    let mut leaders = vec![false; program.len() + 1];
+    leaders[0] = true;
+    for (address, instruction) in program.instructions().iter().enumerate() {
+        if instruction.opcode().control == Control::Jump {
+            leaders[address + 1] = true;
+            if let Flow::To(target) = flows[address] {
+                leaders[target] = true;
+            }
+        }
+    }
+    leaders[program.len()] = true;
+
+    let starts = (0..=program.len())
+        .filter(|address| leaders[*address])
+        .collect::<Vec<_>>();
+    starts
+        .windows(2)
+        .map(|window| BasicBlock {
+            start: window[0],
+            end: window[1],
+            exit: flows[window[1] - 1],
+        })
+        .collect()
+}
+
+/// Addresses of every instruction that can never be run, in order
+pub fn unreachable(program: &Program) -> Vec<usize> {
+    let flows = flows(program);
+    let mut reached = vec![false; program.len()];
+    let mut address = 0;
+    while address < program.len() && !reached[address] {
+        reached[address] = true;
+        match flows[address] {
+            Flow::To(next) => address = next,
+            // there's no telling where a register jump goes, so it could reach anything
+            Flow::Unknown => return Vec::new(),
+            Flow::Halt | Flow::Fault => break,
+        }
+    }
+    (0..program.len()).filter(|a| !reached[*a]).collect()
+}
+
+/// Every loop in the program whether it can be reached or not,
+/// each listed in the order it runs starting from its lowest address
+/// loops are sorted by their lowest address
+pub fn loops(program: &Program) -> Vec<Vec<usize>> {
+    let flows = flows(program);
+
+    // each instruction only has one place to go next, so following the flow from every unvisited address
+    // either joins a path we've already followed or finds a new loop
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        Unvisited,
+        OnPath,
+        Done,
+    }
+    let mut visits = vec![Visit::Unvisited; program.len()];
+    let mut loops = Vec::new();
+    for start in 0..program.len() {
+        let mut path = Vec::new();
+        let mut next = Some(start);
+        while let Some(address) = next.filter(|a| visits[*a] == Visit::Unvisited) {
+            visits[address] = Visit::OnPath;
+            path.push(address);
+            next = match flows[address] {
+                Flow::To(next) => Some(next),
+                _ => None,
+            };
+        }
+        if let Some(address) = next.filter(|a| visits[*a] == Visit::OnPath) {
+            let position = path.iter().position(|a| *a == address).unwrap();
+            let mut cycle = path[position..].to_vec();
+            let lowest = (0..cycle.len()).min_by_key(|i| cycle[*i]).unwrap();
+            cycle.rotate_left(lowest);
+            loops.push(cycle);
+        }
+        for address in path {
+            visits[address] = Visit::Done;
+        }
+    }
+    loops.sort_unstable();
+    loops
+}
+
+/// What happens when the program is run, worked out without running it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Termination {
+    Halts,
+    /// the program ends up going round this loop forever
+    Loops(Vec<usize>),
+    /// the instruction at address jumps out of bounds
+    Faults(usize),
+    /// the instruction at address jumps by the value of a register, so we'd need to run the program to find out
+    Unknown(usize),
+}
+
+/// Whether the program halts, and if not why not
+pub fn terminates(program: &Program) -> Termination {
+    let flows = flows(program);
+    let mut visited = vec![false; program.len()];
+    let mut path = Vec::new();
+    let mut address = 0;
+    loop {
+        if address == program.len() {
+            return Termination::Halts;
+        }
+        if visited[address] {
+            let position = path.iter().position(|a| *a == address).unwrap();
+            return Termination::Loops(path[position..].to_vec());
+        }
+        visited[address] = true;
+        path.push(address);
+        match flows[address] {
+            Flow::To(next) => address = next,
+            Flow::Halt => return Termination::Halts,
+            Flow::Fault => return Termination::Faults(address),
+            Flow::Unknown => return Termination::Unknown(address),
+        }
+    }
+}
+
+/// For each address, whether running the program from there halts
+pub fn halting(program: &Program) -> Vec<bool> {
+    // the control flow graph, but backwards: every address that leads to each address
+    let mut predecessors = vec![Vec::new(); program.len()];
+    let mut halts = vec![false; program.len()];
+    let mut stack = Vec::new();
+    for (address, flow) in flows(program).into_iter().enumerate() {
+        match flow {
+            Flow::To(next) => predecessors[next].push(address),
+            Flow::Halt => {
+                halts[address] = true;
+                stack.push(address);
+            }
+            Flow::Fault | Flow::Unknown => {}
+        }
+    }
+
+    // anything that leads to an address that halts also halts
+    while let Some(address) = stack.pop() {
+        for &previous in &predecessors[address] {
+            if !halts[previous] {
+                halts[previous] = true;
+                stack.push(previous);
+            }
+        }
+    }
+    halts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::console::{test_program, InstructionSet, TEST_PROGRAM};
+
+    fn parse(program: &str) -> Program {
+        InstructionSet::handheld().parse_program(program).unwrap()
+    }
+
+    #[test]
+    fn basic_blocks_work() {
+        let block = |start, end, exit| BasicBlock { start, end, exit };
+        // the jmp at 4 goes to 1, so the first block is split there
+        assert_eq!(
+            vec![
+                block(0, 1, Flow::To(1)),
+                block(1, 3, Flow::To(6)),
+                block(3, 5, Flow::To(1)),
+                block(5, 6, Flow::To(6)),
+                block(6, 8, Flow::To(3)),
+                block(8, 9, Flow::Halt),
+            ],
+            basic_blocks(&test_program())
+        );
+        assert!(basic_blocks(&Program::default()).is_empty());
+    }
+
+    #[test]
+    fn unreachable_works() {
+        assert_eq!(vec![5, 8], unreachable(&test_program()));
+        assert!(unreachable(&parse("nop +0\nacc +1")).is_empty());
+    }
+
+    #[test]
+    fn loops_work() {
+        assert_eq!(vec![vec![1, 2, 6, 7, 3, 4]], loops(&test_program()));
+
+        let program = parse("jmp +2\njmp +0\njmp -2\nacc +1\nnop +0\njmp -2");
+        assert_eq!(vec![vec![0, 2], vec![1], vec![3, 4, 5]], loops(&program));
+    }
+
+    #[test]
+    fn terminates_works() {
+        assert_eq!(
+            Termination::Loops(vec![1, 2, 6, 7, 3, 4]),
+            terminates(&test_program())
+        );
+        let fixed = TEST_PROGRAM.replace("jmp -4", "nop -4");
+        assert_eq!(Termination::Halts, terminates(&parse(&fixed)));
+        assert_eq!(Termination::Faults(1), terminates(&parse("nop +0\njmp -5")));
+        assert_eq!(Termination::Unknown(1), terminates(&parse("acc +1\njmp a")));
+        assert_eq!(Termination::Halts, terminates(&Program::default()));
+    }
+
+    #[test]
+    fn halting_works() {
+        let fixed = TEST_PROGRAM.replace("jmp -4", "nop -4");
+        assert_eq!(vec![true; 9], halting(&parse(&fixed)));
+        assert_eq!(
+            vec![false, false, false, false, false, false, false, false, true],
+            halting(&test_program())
+        );
+    }
+}
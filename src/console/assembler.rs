@@ -0,0 +1,192 @@
+//! Assembling programs written by hand, with comments and labels, see [`assemble`]
+
+use std::collections::HashMap;
+
+use super::{InstructionSet, ParseError, Program};
+
+/// Why a program could not be assembled
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssembleError {
+    /// labels are made of letters, digits and underscores and can't be a single letter (which would be a register)
+    InvalidLabel(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+    Instruction(ParseError),
+}
+
+impl std::error::Error for AssembleError {}
+
+impl std::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AssembleError::InvalidLabel(label) => write!(f, "invalid label: {:?}", label),
+            AssembleError::DuplicateLabel(label) => write!(f, "label {} is already defined", label),
+            AssembleError::UnknownLabel(label) => write!(f, "unknown label: {}", label),
+            AssembleError::Instruction(error) => error.fmt(f),
+        }
+    }
+}
+
+/// An AssembleError and the line of source (starting at 1) it happened on
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssembleLineError {
+    pub line: usize,
+    pub error: AssembleError,
+}
+
+impl std::error::Error for AssembleLineError {}
+
+impl std::fmt::Display for AssembleLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    s.len() > 1 && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Assembles source code into a Program, e.g.
+///
+/// ```text
+/// # add 1 to the accumulator forever
+/// loop: acc +1
+///       jmp loop
+/// ```
+///
+/// Everything after a `#` is a comment, and blank lines are ignored.
+/// A line may start with `label:` to name the address of the instruction that follows, on the same line or a later one.
+/// An operand that names a label becomes the offset from the instruction to that label, so `jmp loop` jumps to it.
+pub fn assemble(set: &InstructionSet, source: &str) -> Result<Program, AssembleLineError> {
+    // first pass: strip comments and labels, recording the address of each label
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let error = |error| AssembleLineError { line: i + 1, error };
+        let mut line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(colon) = line.find(':') {
+            let label = line[..colon].trim();
+            if !is_label(label) {
+                return Err(error(AssembleError::InvalidLabel(label.to_string())));
+            }
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(error(AssembleError::DuplicateLabel(label.to_string())));
+            }
+            line = line[colon + 1..].trim();
+        }
+        if !line.is_empty() {
+            lines.push((i + 1, line));
+        }
+    }
+
+    // second pass: swap labels for offsets and parse the instructions
+    let instructions = lines
+        .into_iter()
+        .enumerate()
+        .map(|(address, (line, text))| {
+            let error = |error| AssembleLineError { line, error };
+            let mut iter = text.splitn(2, ' ');
+            let mnemonic = iter.next().unwrap_or_default();
+            let operands = iter
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|operand| !operand.is_empty())
+                .map(|operand| {
+                    if !is_label(operand) || operand.parse::<i64>().is_ok() {
+                        return Ok(operand.to_string());
+                    }
+                    match labels.get(operand) {
+                        Some(&target) => Ok(format!("{:+}", target as i64 - address as i64)),
+                        None => Err(error(AssembleError::UnknownLabel(operand.to_string()))),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            set.parse_instruction(&format!("{} {}", mnemonic, operands.join(", ")))
+                .map_err(|e| error(AssembleError::Instruction(e)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Program::new(instructions))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::console::{Machine, Status};
+
+    #[test]
+    fn assemble_works() {
+        let source = "\
+# count to 3 then stop
+start:
+    acc +1      # one
+    nop +0
+loop: acc +1
+    jmp end     # skip the infinite loop
+forever: jmp forever
+
+end: jmp start_again
+start_again: acc +1
+";
+        let set = InstructionSet::handheld();
+        let program = assemble(&set, source).unwrap();
+        assert_eq!(
+            "acc +1\nnop +0\nacc +1\njmp +2\njmp +0\njmp +1\nacc +1\n",
+            program.to_string()
+        );
+
+        let mut machine = Machine::new();
+        assert_eq!(&Status::Halted, machine.run(&program));
+        assert_eq!(3, machine.accumulator());
+    }
+
+    #[test]
+    fn backwards_labels() {
+        let set = InstructionSet::handheld();
+        let program = assemble(&set, "top: nop +0\nacc +1\njmp top").unwrap();
+        assert_eq!("jmp -2", program.get(2).unwrap().to_string());
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let set = InstructionSet::handheld();
+        let error = |source| assemble(&set, source).unwrap_err();
+
+        assert_eq!(
+            AssembleLineError {
+                line: 2,
+                error: AssembleError::UnknownLabel("nowhere".to_string())
+            },
+            error("nop +0\njmp nowhere")
+        );
+        assert_eq!(
+            AssembleLineError {
+                line: 3,
+                error: AssembleError::DuplicateLabel("here".to_string())
+            },
+            error("here: nop +0\n\nhere: nop +0")
+        );
+        assert_eq!(
+            AssembleError::InvalidLabel("b".to_string()),
+            error("b: nop +0").error
+        );
+        assert_eq!(
+            AssembleError::InvalidLabel("two words".to_string()),
+            error("two words: nop +0").error
+        );
+        assert_eq!(
+            "line 2: unknown instruction: \"hcf\"",
+            error("# comment\nhcf +0").to_string()
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let set = InstructionSet::handheld();
+        let program = set.parse_program("nop +0\nacc -1\njmp +4").unwrap();
+        assert_eq!("nop +0\nacc -1\njmp +4\n", program.to_string());
+        assert_eq!(program, set.parse_program(&program.to_string()).unwrap());
+    }
+}
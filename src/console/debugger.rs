@@ -0,0 +1,349 @@
+//! Stepping through a Program interactively, see [`Debugger`] and [`repl`]
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use super::{Machine, Program, Status};
+
+/// Why the debugger handed control back
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pause {
+    /// a single instruction was run and the machine can keep going
+    Step,
+    /// the instruction at this address has a breakpoint and is about to run
+    Breakpoint(usize),
+    /// the accumulator changed while running the instruction at address
+    Watch { address: usize, old: i64, new: i64 },
+    /// the machine stopped, stepping or continuing won't do anything until it is restarted
+    Stopped(Status),
+}
+
+/// Runs a Machine over a Program, stopping at breakpoints and (optionally) whenever the accumulator changes
+pub struct Debugger<'a> {
+    program: &'a Program,
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    watch_accumulator: bool,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Debugger {
+            program,
+            machine: Machine::new(),
+            breakpoints: BTreeSet::new(),
+            watch_accumulator: false,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// returns false if there was already a breakpoint at address
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// returns false if there was no breakpoint at address
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    /// when watching, stepping or continuing pauses after any instruction that changes the accumulator
+    pub fn watch_accumulator(&mut self, watch: bool) {
+        self.watch_accumulator = watch;
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watch_accumulator
+    }
+
+    /// starts the program again from the beginning, breakpoints are kept
+    pub fn restart(&mut self) {
+        self.machine.reset();
+    }
+
+    /// runs a single instruction
+    pub fn step(&mut self) -> Pause {
+        let address = self.machine.instruction_pointer();
+        let old = self.machine.accumulator();
+        if self.machine.step(self.program) != &Status::Running {
+            return Pause::Stopped(self.machine.status().clone());
+        }
+        let new = self.machine.accumulator();
+        if self.watch_accumulator && old != new {
+            Pause::Watch { address, old, new }
+        } else {
+            Pause::Step
+        }
+    }
+
+    /// runs until the next breakpoint, the watched accumulator changes, or the machine stops
+    /// always runs at least one instruction, so continuing from a breakpoint doesn't stop at it again
+    pub fn resume(&mut self) -> Pause {
+        loop {
+            match self.step() {
+                Pause::Step => {}
+                pause => return pause,
+            }
+            let address = self.machine.instruction_pointer();
+            if self.breakpoints.contains(&address) {
+                return Pause::Breakpoint(address);
+            }
+        }
+    }
+
+    /// runs until the machine stops, ignoring breakpoints and watches
+    /// for the handheld this is usually because it found an infinite loop
+    pub fn run_to_loop(&mut self) -> Status {
+        while self.machine.step(self.program) == &Status::Running {}
+        self.machine.status().clone()
+    }
+
+    /// Lists every instruction with its address, `=>` marks the instruction pointer and `*` marks breakpoints
+    pub fn disassemble(&self) -> String {
+        let ip = self.machine.instruction_pointer();
+        let width = self.program.len().saturating_sub(1).to_string().len();
+        self.program
+            .instructions()
+            .iter()
+            .enumerate()
+            .map(|(address, instruction)| {
+                format!(
+                    "{} {}{:>width$}: {}\n",
+                    if address == ip { "=>" } else { "  " },
+                    if self.breakpoints.contains(&address) {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    address,
+                    instruction,
+                    width = width,
+                )
+            })
+            .collect()
+    }
+}
+
+const HELP: &str = "\
+commands:
+  s, step          run one instruction
+  c, continue      run until a breakpoint, a watch or the program stops
+  r, run           run until the program stops, ignoring breakpoints
+  b, break <addr>  add a breakpoint
+  d, delete <addr> remove a breakpoint
+  w, watch         toggle pausing whenever the accumulator changes
+  l, list          show the program
+  p, print         show the instruction pointer and accumulator
+  restart          start the program again
+  h, help          show this message
+  q, quit          leave the debugger
+";
+
+/// Drives a Debugger with one command per line read from input, writing results to output
+/// Stops at the end of input or on `quit`
+pub fn repl<R: BufRead, W: Write>(
+    debugger: &mut Debugger,
+    input: R,
+    mut output: W,
+) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let address = words.next().map(|word| word.parse::<usize>());
+
+        match (command, address) {
+            ("s", None) | ("step", None) => {
+                let pause = debugger.step();
+                write_pause(&mut output, &pause)?;
+            }
+            ("c", None) | ("continue", None) => {
+                let pause = debugger.resume();
+                write_pause(&mut output, &pause)?;
+            }
+            ("r", None) | ("run", None) => {
+                let status = debugger.run_to_loop();
+                write_pause(&mut output, &Pause::Stopped(status))?;
+            }
+            ("b", Some(Ok(address))) | ("break", Some(Ok(address))) => {
+                debugger.add_breakpoint(address);
+                writeln!(output, "breakpoint at {}", address)?;
+            }
+            ("d", Some(Ok(address))) | ("delete", Some(Ok(address))) => {
+                if debugger.remove_breakpoint(address) {
+                    writeln!(output, "removed breakpoint at {}", address)?;
+                } else {
+                    writeln!(output, "no breakpoint at {}", address)?;
+                }
+            }
+            ("w", None) | ("watch", None) => {
+                debugger.watch_accumulator(!debugger.is_watching());
+                let state = if debugger.is_watching() { "on" } else { "off" };
+                writeln!(output, "watching accumulator: {}", state)?;
+            }
+            ("l", None) | ("list", None) => write!(output, "{}", debugger.disassemble())?,
+            ("p", None) | ("print", None) => writeln!(
+                output,
+                "ip: {}, acc: {}",
+                debugger.machine().instruction_pointer(),
+                debugger.machine().accumulator()
+            )?,
+            ("restart", None) => {
+                debugger.restart();
+                writeln!(output, "restarted")?;
+            }
+            ("h", None) | ("help", None) => write!(output, "{}", HELP)?,
+            ("q", None) | ("quit", None) => break,
+            _ => writeln!(output, "invalid command: {}, try help", line.trim())?,
+        }
+    }
+    Ok(())
+}
+
+fn write_pause<W: Write>(output: &mut W, pause: &Pause) -> std::io::Result<()> {
+    match pause {
+        Pause::Step => writeln!(output, "stepped"),
+        Pause::Breakpoint(address) => writeln!(output, "hit breakpoint at {}", address),
+        Pause::Watch { address, old, new } => {
+            writeln!(output, "acc changed at {}: {} -> {}", address, old, new)
+        }
+        Pause::Stopped(Status::Running) => unreachable!("a stopped machine can't be running"),
+        Pause::Stopped(Status::Halted) => writeln!(output, "program halted"),
+        Pause::Stopped(Status::Looped) => writeln!(output, "infinite loop detected"),
+        Pause::Stopped(Status::Fault { address, fault }) => {
+            writeln!(output, "fault at {}: {}", address, fault)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::console::{test_program, InstructionSet};
+
+    #[test]
+    fn stepping_works() {
+        let program = test_program();
+        let mut debugger = Debugger::new(&program);
+
+        assert_eq!(Pause::Step, debugger.step());
+        assert_eq!(Pause::Step, debugger.step());
+        assert_eq!(1, debugger.machine().accumulator());
+        assert_eq!(2, debugger.machine().instruction_pointer());
+    }
+
+    #[test]
+    fn breakpoints_work() {
+        let program = test_program();
+        let mut debugger = Debugger::new(&program);
+        debugger.add_breakpoint(6);
+        debugger.add_breakpoint(3);
+
+        assert_eq!(Pause::Breakpoint(6), debugger.resume());
+        assert_eq!(Pause::Breakpoint(3), debugger.resume());
+        assert_eq!(2, debugger.machine().accumulator());
+
+        assert!(debugger.remove_breakpoint(3));
+        assert!(!debugger.remove_breakpoint(3));
+        assert_eq!(Pause::Stopped(Status::Looped), debugger.resume());
+        assert_eq!(Pause::Stopped(Status::Looped), debugger.step());
+        assert_eq!(vec![6], debugger.breakpoints().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn watch_works() {
+        let program = test_program();
+        let mut debugger = Debugger::new(&program);
+        debugger.watch_accumulator(true);
+
+        assert_eq!(
+            Pause::Watch {
+                address: 1,
+                old: 0,
+                new: 1
+            },
+            debugger.resume()
+        );
+        assert_eq!(
+            Pause::Watch {
+                address: 6,
+                old: 1,
+                new: 2
+            },
+            debugger.resume()
+        );
+    }
+
+    #[test]
+    fn run_to_loop_works() {
+        let program = test_program();
+        let mut debugger = Debugger::new(&program);
+        debugger.add_breakpoint(4);
+
+        assert_eq!(Status::Looped, debugger.run_to_loop());
+        assert_eq!(5, debugger.machine().accumulator());
+
+        debugger.restart();
+        assert_eq!(0, debugger.machine().accumulator());
+        assert_eq!(Pause::Breakpoint(4), debugger.resume());
+    }
+
+    #[test]
+    fn disassemble_works() {
+        let program = InstructionSet::handheld()
+            .parse_program("nop +0\nacc +1\njmp -2")
+            .unwrap();
+        let mut debugger = Debugger::new(&program);
+        debugger.add_breakpoint(2);
+        debugger.step();
+
+        assert_eq!(
+            "    0: nop +0\n=>  1: acc +1\n   *2: jmp -2\n",
+            debugger.disassemble()
+        );
+    }
+
+    #[test]
+    fn repl_works() {
+        let program = test_program();
+        let mut debugger = Debugger::new(&program);
+        let input = "b 3\ncontinue\np\nw\nc\nbreak x\nr\nq\ns\n";
+        let mut output = Vec::new();
+
+        repl(&mut debugger, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            "\
+breakpoint at 3
+hit breakpoint at 3
+ip: 3, acc: 2
+watching accumulator: on
+acc changed at 3: 2 -> 5
+invalid command: break x, try help
+infinite loop detected
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        // toggling starts from the debugger's own watch state
+        let mut debugger = Debugger::new(&program);
+        debugger.watch_accumulator(true);
+        let mut output = Vec::new();
+        repl(&mut debugger, "w\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            "watching accumulator: off\n",
+            String::from_utf8(output).unwrap()
+        );
+        assert!(!debugger.is_watching());
+    }
+}
@@ -0,0 +1,250 @@
+//! Traces of the instructions a Machine ran, saved and loaded as JSON lines so that runs can be compared
+
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Instruction, InstructionSet, ParseError};
+
+/// A single instruction run by a Machine
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// the address of the instruction
+    pub ip: usize,
+    pub instruction: Instruction,
+    pub acc_before: i64,
+    pub acc_after: i64,
+}
+
+/// How a Step is written as JSON, instructions are written as they would appear in a program
+#[derive(Serialize, Deserialize)]
+struct JsonStep {
+    ip: usize,
+    instruction: String,
+    acc_before: i64,
+    acc_after: i64,
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(std::io::Error),
+    /// a line (starting at 1) that wasn't a JSON step
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+    /// a line (starting at 1) whose instruction couldn't be parsed
+    Instruction {
+        line: usize,
+        error: ParseError,
+    },
+}
+
+impl std::error::Error for TraceError {}
+
+impl std::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TraceError::Io(error) => error.fmt(f),
+            TraceError::Json { line, error } => write!(f, "line {}: {}", line, error),
+            TraceError::Instruction { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl From<std::io::Error> for TraceError {
+    fn from(error: std::io::Error) -> Self {
+        TraceError::Io(error)
+    }
+}
+
+/// Writes each step as a JSON object on its own line
+pub fn write_json_lines<W: Write>(trace: &[Step], mut output: W) -> std::io::Result<()> {
+    for step in trace {
+        let json = JsonStep {
+            ip: step.ip,
+            instruction: step.instruction.to_string(),
+            acc_before: step.acc_before,
+            acc_after: step.acc_after,
+        };
+        serde_json::to_writer(&mut output, &json)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Reads a trace written by write_json_lines, instructions are parsed using set
+/// blank lines are skipped
+pub fn read_json_lines<R: BufRead>(
+    set: &InstructionSet,
+    input: R,
+) -> Result<Vec<Step>, TraceError> {
+    let mut trace = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let json: JsonStep =
+            serde_json::from_str(&line).map_err(|error| TraceError::Json { line: i + 1, error })?;
+        let instruction = set
+            .parse_instruction(&json.instruction)
+            .map_err(|error| TraceError::Instruction { line: i + 1, error })?;
+        trace.push(Step {
+            ip: json.ip,
+            instruction,
+            acc_before: json.acc_before,
+            acc_after: json.acc_after,
+        });
+    }
+    Ok(trace)
+}
+
+/// The first step at which two traces ran a different instruction, or at a different address
+/// left or right are None if that trace ended first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence<'a> {
+    pub step: usize,
+    pub left: Option<&'a Step>,
+    pub right: Option<&'a Step>,
+}
+
+/// Finds where control flow differs between two traces, None if they ran the same instructions in the same order
+/// the accumulator is ignored, only the instructions run matter
+pub fn diverge<'a>(left: &'a [Step], right: &'a [Step]) -> Option<Divergence<'a>> {
+    let same = |a: &Step, b: &Step| a.ip == b.ip && a.instruction == b.instruction;
+    (0..left.len().max(right.len()))
+        .map(|step| Divergence {
+            step,
+            left: left.get(step),
+            right: right.get(step),
+        })
+        .find(|d| match (d.left, d.right) {
+            (Some(a), Some(b)) => !same(a, b),
+            _ => true,
+        })
+}
+
+impl std::fmt::Display for Divergence<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |step: Option<&Step>| match step {
+            Some(step) => format!("ran {}: {}", step.ip, step.instruction),
+            None => "had stopped".to_string(),
+        };
+        write!(
+            f,
+            "step {}: left {}, right {}",
+            self.step,
+            describe(self.left),
+            describe(self.right)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::console::{test_program, Machine, Operand, Program, JMP, NOP};
+
+    fn trace(program: &Program) -> Vec<Step> {
+        let mut machine = Machine::new();
+        machine.record_trace();
+        machine.run(program);
+        machine.trace().unwrap().to_vec()
+    }
+
+    #[test]
+    fn recording_works() {
+        let mut machine = Machine::new();
+        machine.run(&test_program());
+        assert_eq!(None, machine.trace());
+
+        let trace = trace(&test_program());
+        assert_eq!(
+            vec![0, 1, 2, 6, 7, 3, 4],
+            trace.iter().map(|step| step.ip).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Step {
+                ip: 3,
+                instruction: test_program().get(3).unwrap().clone(),
+                acc_before: 2,
+                acc_after: 5
+            },
+            trace[5]
+        );
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let trace = trace(&test_program());
+        let mut output = Vec::new();
+        write_json_lines(&trace, &mut output).unwrap();
+
+        let json = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"{"ip":0,"instruction":"nop +0","acc_before":0,"acc_after":0}"#,
+            json.lines().next().unwrap()
+        );
+
+        let set = InstructionSet::handheld();
+        assert_eq!(trace, read_json_lines(&set, json.as_bytes()).unwrap());
+
+        let error = read_json_lines(&set, "\n{\"ip\":0}".as_bytes()).unwrap_err();
+        assert!(matches!(error, TraceError::Json { line: 2, .. }));
+
+        let bad_instruction = r#"{"ip":0,"instruction":"hcf +0","acc_before":0,"acc_after":0}"#;
+        let error = read_json_lines(&set, bad_instruction.as_bytes()).unwrap_err();
+        assert_eq!("line 1: unknown instruction: \"hcf\"", error.to_string());
+    }
+
+    #[test]
+    fn diverge_works() {
+        let original = test_program();
+        let mut patched = original.clone();
+        patched.replace(7, Instruction::new(&NOP, vec![Operand::Value(-4)]).unwrap());
+
+        let left = trace(&original);
+        let right = trace(&patched);
+        let divergence = diverge(&left, &right).unwrap();
+        assert_eq!(4, divergence.step);
+        assert_eq!(
+            "step 4: left ran 7: jmp -4, right ran 7: nop -4",
+            divergence.to_string()
+        );
+
+        assert_eq!(None, diverge(&left, &left));
+
+        let divergence = diverge(&left, &left[..3]).unwrap();
+        assert_eq!(
+            "step 3: left ran 6: acc +1, right had stopped",
+            divergence.to_string()
+        );
+    }
+
+    #[test]
+    fn faults_are_recorded() {
+        let original = test_program();
+        let mut patched = original.clone();
+        patched.replace(7, Instruction::new(&JMP, vec![Operand::Value(-8)]).unwrap());
+
+        // the jmp that goes out of bounds is the last step
+        let right = trace(&patched);
+        assert_eq!(
+            Some(&Step {
+                ip: 7,
+                instruction: patched.get(7).unwrap().clone(),
+                acc_before: 2,
+                acc_after: 2
+            }),
+            right.last()
+        );
+
+        let left = trace(&original);
+        assert_eq!(
+            "step 4: left ran 7: jmp -4, right ran 7: jmp -8",
+            diverge(&left, &right).unwrap().to_string()
+        );
+    }
+}
@@ -1,48 +1,190 @@
-use aoc_runner_derive::aoc;
+use aoc_runner_derive::{aoc, aoc_generator};
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
-#[aoc(day6, part1)]
-pub fn part1(input: &str) -> usize {
-    input
-        .split("\n\n")
-        .map(|group| {
-            let mut unique_answers = HashSet::new();
-            for person in group.lines() {
-                for answer in person.chars() {
-                    unique_answers.insert(answer);
-                }
+use crate::records::records;
+
+/// A group of people, each person is the set of questions they answered "yes" to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    people: Vec<HashSet<char>>,
+}
+
+impl Group {
+    /// the number of people in the group
+    pub fn size(&self) -> usize {
+        self.people.len()
+    }
+
+    /// questions that anyone in the group answered "yes" to
+    pub fn anyone(&self) -> HashSet<char> {
+        self.people.iter().flatten().cloned().collect()
+    }
+
+    /// questions that everyone in the group answered "yes" to
+    /// an empty group has no questions that everyone answered
+    pub fn everyone(&self) -> HashSet<char> {
+        match self.people.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.clone(), |acc, set| {
+                acc.intersection(set).cloned().collect()
+            }),
+            None => HashSet::new(),
+        }
+    }
+}
+
+/// How often a single question was answered "yes"
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuestionStatistics {
+    /// number of groups where at least one person answered "yes"
+    pub groups: usize,
+    /// number of people who answered "yes"
+    pub people: usize,
+}
+
+/// Statistics gathered across every group
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    groups: usize,
+    people: usize,
+    questions: BTreeMap<char, QuestionStatistics>,
+    group_sizes: BTreeMap<usize, usize>,
+}
+
+impl Statistics {
+    pub fn new(groups: &[Group]) -> Self {
+        let mut stats = Statistics::default();
+        for group in groups {
+            stats.groups += 1;
+            stats.people += group.size();
+            *stats.group_sizes.entry(group.size()).or_insert(0) += 1;
+
+            for question in group.anyone() {
+                stats.questions.entry(question).or_default().groups += 1;
+            }
+            for question in group.people.iter().flatten() {
+                stats.questions.entry(*question).or_default().people += 1;
             }
-            unique_answers
+        }
+        stats
+    }
+
+    /// statistics for a single question, None if nobody answered "yes" to it
+    pub fn question(&self, question: char) -> Option<QuestionStatistics> {
+        self.questions.get(&question).cloned()
+    }
+
+    /// the questions answered "yes" by the most people (more than one if tied)
+    pub fn most_common(&self) -> Vec<char> {
+        let max = self.questions.values().map(|q| q.people).max();
+        self.questions_with(max)
+    }
+
+    /// the questions answered "yes" by the fewest people (more than one if tied)
+    /// questions nobody answered "yes" to are not counted
+    pub fn least_common(&self) -> Vec<char> {
+        let min = self.questions.values().map(|q| q.people).min();
+        self.questions_with(min)
+    }
+
+    fn questions_with(&self, people: Option<usize>) -> Vec<char> {
+        self.questions
+            .iter()
+            .filter(|(_, q)| Some(q.people) == people)
+            .map(|(c, _)| *c)
+            .collect()
+    }
+
+    /// maps group size to the number of groups of that size
+    pub fn group_sizes(&self) -> &BTreeMap<usize, usize> {
+        &self.group_sizes
+    }
+}
+
+impl std::fmt::Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} groups, {} people", self.groups, self.people)?;
+        writeln!(f)?;
+        writeln!(f, "question | groups | people")?;
+        writeln!(f, "---------+--------+-------")?;
+        for (question, stats) in &self.questions {
+            writeln!(
+                f,
+                "{:<8} | {:>6} | {:>6}",
+                question, stats.groups, stats.people
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "group size | groups")?;
+        writeln!(f, "-----------+-------")?;
+        for (size, count) in &self.group_sizes {
+            writeln!(f, "{:>10} | {:>6}", size, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// An answer that wasn't a question letter from a to z
+#[derive(Debug, PartialEq)]
+pub struct AnswerError {
+    line: usize,
+    answer: char,
+}
+
+impl std::error::Error for AnswerError {}
+
+impl std::fmt::Display for AnswerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}: invalid answer {:?}, answers must be lowercase letters a-z",
+            self.line, self.answer
+        )
+    }
+}
+
+#[aoc_generator(day6)]
+pub fn input_generator(input: &str) -> Result<Vec<Group>, AnswerError> {
+    records(input)
+        .map(|record| {
+            let people = record
+                .lines
+                .iter()
+                .enumerate()
+                .map(|(i, person)| {
+                    person
+                        .trim()
+                        .chars()
+                        .map(|answer| match answer {
+                            'a'..='z' => Ok(answer),
+                            _ => Err(AnswerError {
+                                line: record.line + i,
+                                answer,
+                            }),
+                        })
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Group { people })
         })
-        .map(|x| x.len())
-        .sum()
+        .collect()
+}
+
+#[aoc(day6, part1)]
+pub fn part1(groups: &[Group]) -> usize {
+    groups.iter().map(|group| group.anyone().len()).sum()
 }
 
 #[aoc(day6, part2)]
-pub fn part2(input: &str) -> usize {
-    input
-        .split("\n\n")
-        .map(|group| {
-            let mut group_iter = group.lines().map(|person| {
-                // each person is a set of answers
-                person.chars().collect::<HashSet<char>>()
-            });
-            // we want to return the intersecting answers of all the people in each group
-            let intersecting_answers = group_iter.next().unwrap();
-            group_iter.fold(intersecting_answers, |acc, set| {
-                acc.intersection(&set).cloned().collect()
-            })
-        })
-        .map(|x| x.len())
-        .sum()
+pub fn part2(groups: &[Group]) -> usize {
+    groups.iter().map(|group| group.everyone().len()).sum()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const TEST_INPUT: &'static str = "\
+    const TEST_INPUT: &str = "\
 abc
 
 a
@@ -61,11 +203,89 @@ b";
 
     #[test]
     fn part1_works() {
-        assert_eq!(11, part1(&TEST_INPUT));
+        assert_eq!(11, part1(&input_generator(TEST_INPUT).unwrap()));
     }
 
     #[test]
     fn part2_works() {
-        assert_eq!(6, part2(&TEST_INPUT));
+        assert_eq!(6, part2(&input_generator(TEST_INPUT).unwrap()));
+    }
+
+    #[test]
+    fn irregular_blank_lines() {
+        let input = "\r\nabc\r\n\r\n\r\na\r\nb\r\n\n\n\n";
+        let groups = input_generator(input).unwrap();
+        assert_eq!(2, groups.len());
+        assert_eq!(5, part1(&groups));
+        assert_eq!(3, part2(&groups));
+    }
+
+    #[test]
+    fn invalid_answers() {
+        assert_eq!(
+            Err(AnswerError {
+                line: 4,
+                answer: 'B'
+            }),
+            input_generator("abc\n\na\naB")
+        );
+        assert_eq!(
+            "line 1: invalid answer '1', answers must be lowercase letters a-z",
+            input_generator("a1").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn statistics_works() {
+        let stats = Statistics::new(&input_generator(TEST_INPUT).unwrap());
+
+        assert_eq!(
+            Some(QuestionStatistics {
+                groups: 4,
+                people: 8
+            }),
+            stats.question('a')
+        );
+        assert_eq!(
+            Some(QuestionStatistics {
+                groups: 4,
+                people: 4
+            }),
+            stats.question('b')
+        );
+        assert_eq!(None, stats.question('z'));
+
+        assert_eq!(vec!['a'], stats.most_common());
+        assert_eq!(vec!['c'], stats.least_common());
+
+        assert_eq!(
+            vec![(1, 2), (2, 1), (3, 1), (4, 1)],
+            stats
+                .group_sizes()
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn statistics_table() {
+        let stats = Statistics::new(&input_generator("ab\nb\n\nb").unwrap());
+        assert_eq!(
+            "\
+2 groups, 3 people
+
+question | groups | people
+---------+--------+-------
+a        |      1 |      1
+b        |      2 |      3
+
+group size | groups
+-----------+-------
+         1 |      1
+         2 |      1
+",
+            stats.to_string()
+        );
     }
 }
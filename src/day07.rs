@@ -1,16 +1,60 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 
+use petgraph::algo::toposort;
+use petgraph::dot::Dot;
 use petgraph::graph::{Graph, NodeIndex};
-use petgraph::visit::{Bfs, DfsPostOrder, EdgeRef};
+use petgraph::visit::{Bfs, EdgeRef, IntoNeighbors, NodeFiltered, Reversed, Visitable};
+use petgraph::Direction;
+
+/// The color of bag our puzzle answers are about
+const SHINY_GOLD: &str = "shiny gold";
+
+/// Errors from querying a BagGraph
+#[derive(Clone, Debug, PartialEq)]
+pub enum BagError {
+    /// no rule mentions a bag of this color
+    UnknownColor(String),
+    /// bags that eventually contain themselves, each bag directly contains the next
+    /// and the last bag directly contains the first
+    Cycle(Vec<String>),
+    /// the total number of bags inside this color doesn't fit in a usize
+    Overflow(String),
+}
+
+impl std::error::Error for BagError {}
+
+impl std::fmt::Display for BagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BagError::UnknownColor(color) => write!(f, "unknown bag color: {}", color),
+            BagError::Cycle(colors) => {
+                // the cycle ends back at the first bag
+                let path = colors
+                    .iter()
+                    .chain(colors.first())
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                write!(f, "bags contain themselves: {}", path.join(" -> "))
+            }
+            BagError::Overflow(color) => write!(f, "too many bags inside {} to count", color),
+        }
+    }
+}
 
 /// Used to help build our graph of Bags piece by piece.
 /// It stores the index each color String is stored at in the Graph
 /// and implements helper methods
+/// contents counts are remembered across queries and forgotten whenever an edge is added
+#[derive(Default)]
 pub struct BagGraph {
     graph: Graph<String, usize>,
     node_map: HashMap<String, NodeIndex>,
+    contents_counts: RefCell<HashMap<NodeIndex, usize>>,
 }
 
 impl BagGraph {
@@ -18,6 +62,7 @@ impl BagGraph {
         BagGraph {
             graph: Graph::new(),
             node_map: HashMap::new(),
+            contents_counts: RefCell::new(HashMap::new()),
         }
     }
 
@@ -26,6 +71,12 @@ impl BagGraph {
         &self.graph
     }
 
+    /// return a view of the graph with every edge reversed,
+    /// i.e. directed from a bag up to the bags that directly contain it
+    pub fn containers_graph(&self) -> Reversed<&Graph<String, usize>> {
+        Reversed(&self.graph)
+    }
+
     /// Makes no change if the bag is already in the graph
     /// Adds a bag to the graph and records its index
     /// Always returns the NodeIndex of the bag
@@ -47,103 +98,420 @@ impl BagGraph {
         let a = self.add_bag(color);
         let b = self.add_bag(contained_color);
         self.graph.add_edge(a, b, number);
+        self.contents_counts.get_mut().clear();
     }
 
     fn lookup_node_index(&self, color: &str) -> Option<NodeIndex> {
         self.node_map.get(color).cloned()
     }
-}
 
-#[aoc_generator(day7)]
-pub fn input_generator(input: &str) -> BagGraph {
-    let mut bag_graph = BagGraph::new();
+    fn node_index(&self, color: &str) -> Result<NodeIndex, BagError> {
+        self.lookup_node_index(color)
+            .ok_or_else(|| BagError::UnknownColor(color.to_string()))
+    }
+
+    /// Every bag color that can eventually contain a bag of this color, sorted by color
+    /// This searches up the containers graph, searching down from the bag would find its contents instead
+    pub fn containers_of(&self, color: &str) -> Result<Vec<&str>, BagError> {
+        let idx = self.node_index(color)?;
+        Ok(self.colors(reachable(self.containers_graph(), idx)))
+    }
+
+    /// Every bag color that can be found (at any depth) inside a bag of this color, sorted by color
+    pub fn contents_of(&self, color: &str) -> Result<Vec<&str>, BagError> {
+        let idx = self.node_index(color)?;
+        Ok(self.colors(reachable(&self.graph, idx)))
+    }
+
+    /// The total number of bags inside a bag of this color
+    /// Counts for this bag and every bag inside it are remembered for later queries
+    /// Returns an error if any bag inside it eventually contains itself, or if there are too many bags to count
+    pub fn contents_count(&self, color: &str) -> Result<usize, BagError> {
+        let idx = self.node_index(color)?;
+        if let Some(count) = self.contents_counts.borrow().get(&idx) {
+            return Ok(*count);
+        }
+
+        // only sort the bags we still need to count, so that cycles elsewhere don't matter
+        let mut todo = reachable(&self.graph, idx);
+        todo.insert(idx);
+        let mut memo = self.contents_counts.borrow_mut();
+        todo.retain(|idx| !memo.contains_key(idx));
 
-    input.lines().for_each(|line| {
-        let mut iter = line.split("contain ");
-        let color = iter
-            .next()
-            .unwrap()
-            .split(' ')
-            .take(2)
-            .collect::<Vec<&str>>()
-            .join(" ");
-        let contains = iter.next().unwrap();
-        if contains == "no other bags." {
-            // a bag that contains no other bags
-            bag_graph.add_bag(color.to_string());
-        } else {
-            // a bag containing other bags
-            contains.split(", ").for_each(|string| {
-                let mut contents = string.split(' ');
-                let count = contents.next().unwrap().parse::<usize>().unwrap();
-                let contained_color = contents.take(2).collect::<Vec<&str>>().join(" ");
-                bag_graph.contains_bag(color.to_string(), count, contained_color.to_string());
-            });
+        let order = toposort(
+            &NodeFiltered::from_fn(&self.graph, |idx| todo.contains(&idx)),
+            None,
+        )
+        .map_err(|cycle| BagError::Cycle(self.cycle_through(cycle.node_id())))?;
+
+        // toposort puts outer bags first, we need to count the bags inside them first
+        for outer in order.into_iter().rev() {
+            let mut count: usize = 0;
+            for e in self.graph.edges_directed(outer, Direction::Outgoing) {
+                // each bag adds itself plus its own total contents
+                count = memo[&e.target()]
+                    .checked_add(1)
+                    .and_then(|n| n.checked_mul(*e.weight()))
+                    .and_then(|n| n.checked_add(count))
+                    .ok_or_else(|| BagError::Overflow(self.graph[outer].clone()))?;
+            }
+            memo.insert(outer, count);
+        }
+        Ok(memo[&idx])
+    }
+
+    /// The chain of bags from outer down to inner, where each bag directly contains the next
+    /// Returns Ok(None) if a bag of color outer can never contain a bag of color inner
+    pub fn path_between(&self, outer: &str, inner: &str) -> Result<Option<Vec<&str>>, BagError> {
+        let start = self.node_index(outer)?;
+        let end = self.node_index(inner)?;
+        if start == end {
+            return Ok(Some(vec![self.graph[start].as_str()]));
+        }
+        Ok(self.shortest_path(start, end).map(|path| {
+            path.into_iter()
+                .map(|idx| self.graph[idx].as_str())
+                .collect()
+        }))
+    }
+
+    /// The shortest chain of bags from start down to end, including both
+    /// If start and end are the same this is the shortest cycle through start, and start is included twice
+    fn shortest_path(&self, start: NodeIndex, end: NodeIndex) -> Option<Vec<NodeIndex>> {
+        // breadth first so that we find the shortest chain,
+        // recording where we came from to reconstruct the path at the end
+        let mut came_from: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(idx) = queue.pop_front() {
+            for next in self.graph.neighbors_directed(idx, Direction::Outgoing) {
+                if came_from.contains_key(&next) {
+                    continue;
+                }
+                came_from.insert(next, idx);
+                if next == end {
+                    let mut path = vec![end];
+                    let mut current = end;
+                    while came_from[&current] != start {
+                        current = came_from[&current];
+                        path.push(current);
+                    }
+                    path.push(start);
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
+    /// The colors of the bags in a cycle that idx is part of
+    fn cycle_through(&self, idx: NodeIndex) -> Vec<String> {
+        let mut cycle = self
+            .shortest_path(idx, idx)
+            .expect("toposort reported a node that isn't in a cycle");
+        cycle.pop();
+        cycle
+            .into_iter()
+            .map(|idx| self.graph[idx].clone())
+            .collect()
+    }
+
+    /// Renders the graph in Graphviz DOT format, with each edge labelled by the number of bags contained
+    /// If a color to highlight is given its bag is filled in, the bags that can contain it are drawn in blue
+    /// and the bags that can be found inside it are drawn in red
+    pub fn to_dot(&self, highlight: Option<&str>) -> Result<String, BagError> {
+        let (query, containers, contents) = match highlight {
+            Some(color) => {
+                let idx = self.node_index(color)?;
+                (
+                    Some(idx),
+                    reachable(self.containers_graph(), idx),
+                    reachable(&self.graph, idx),
+                )
+            }
+            None => (None, HashSet::new(), HashSet::new()),
+        };
+
+        let node_attributes = |_, (idx, _): (NodeIndex, &String)| {
+            if Some(idx) == query {
+                "style = filled fillcolor = gold ".to_string()
+            } else if containers.contains(&idx) {
+                "color = blue ".to_string()
+            } else if contents.contains(&idx) {
+                "color = red ".to_string()
+            } else {
+                String::new()
+            }
         };
-    });
+        let edge_attributes = |_, e: petgraph::graph::EdgeReference<usize>| {
+            let is_container = |idx| Some(idx) == query || containers.contains(&idx);
+            let is_content = |idx| Some(idx) == query || contents.contains(&idx);
+            if is_container(e.source()) && is_container(e.target()) {
+                "color = blue ".to_string()
+            } else if is_content(e.source()) && is_content(e.target()) {
+                "color = red ".to_string()
+            } else {
+                String::new()
+            }
+        };
+
+        Ok(
+            Dot::with_attr_getters(&self.graph, &[], &edge_attributes, &node_attributes)
+                .to_string(),
+        )
+    }
+
+    /// A new BagGraph with just this color, the bags that can contain it, the bags that can be found inside it,
+    /// and the rules between all of those bags
+    pub fn subgraph(&self, color: &str) -> Result<BagGraph, BagError> {
+        let idx = self.node_index(color)?;
+        let mut keep = reachable(self.containers_graph(), idx);
+        keep.extend(reachable(&self.graph, idx));
+        keep.insert(idx);
 
-    bag_graph
+        let mut subgraph = BagGraph::new();
+        for idx in self.graph.node_indices().filter(|idx| keep.contains(idx)) {
+            subgraph.add_bag(self.graph[idx].clone());
+        }
+        for e in self.graph.edge_references() {
+            if keep.contains(&e.source()) && keep.contains(&e.target()) {
+                subgraph.contains_bag(
+                    self.graph[e.source()].clone(),
+                    *e.weight(),
+                    self.graph[e.target()].clone(),
+                );
+            }
+        }
+        Ok(subgraph)
+    }
+
+    fn colors(&self, nodes: HashSet<NodeIndex>) -> Vec<&str> {
+        let mut colors = nodes
+            .into_iter()
+            .map(|idx| self.graph[idx].as_str())
+            .collect::<Vec<_>>();
+        colors.sort_unstable();
+        colors
+    }
 }
 
-#[aoc(day7, part1)]
-pub fn part1(graph: &BagGraph) -> usize {
-    let idx = graph.lookup_node_index("shiny gold").unwrap();
-    let graph_ref = &graph.graph();
-    let mut bfs = Bfs::new(graph_ref, idx);
-    let mut count = 0;
-    while let Some(_idx) = bfs.next(graph_ref) {
-        count += 1;
+/// All nodes reachable from start by following the edges of graph, not including start itself
+fn reachable<G>(graph: G, start: NodeIndex) -> HashSet<NodeIndex>
+where
+    G: IntoNeighbors<NodeId = NodeIndex> + Visitable<NodeId = NodeIndex>,
+{
+    let mut bfs = Bfs::new(graph, start);
+    let mut seen = HashSet::new();
+    while let Some(idx) = bfs.next(graph) {
+        if idx != start {
+            seen.insert(idx);
+        }
     }
-    // adjusted to not include shiny gold bags themselves!
-    count - 1
+    seen
 }
 
-#[aoc(day7, part2)]
-pub fn part2(graph: &BagGraph) -> usize {
-    let idx = graph.lookup_node_index("shiny gold").unwrap();
-    let graph_ref = graph.graph();
-
-    // we need post order, i.e. bottom to top.
-    // to calculate the count of a bag, we need know the count of all of the bags inside it first
-    let mut dfs = DfsPostOrder::new(graph_ref, idx);
-
-    // store the counts of each bag as we traverse the graph
-    let mut counts: HashMap<NodeIndex, usize> = HashMap::new();
-
-    // Depth first search where we need to track all the weights as we come up and multiply them together
-    // each bag will add its "count" plus its count multiplied by its total contents
-    while let Some(idx) = dfs.next(graph_ref) {
-        for e in graph_ref.edges_directed(idx, petgraph::Direction::Outgoing) {
-            //     outer (source)
-            //    /
-            //   / e
-            //  /
-            // inner (target)
-            let count = counts.entry(e.source()).or_insert(0);
-            *count += e.weight();
-
-            // dbg!(&graph.graph()[e.source()]);
-            // dbg!(&graph.graph()[e.target()]);
-
-            if let Some(multiplier) = counts.get(&e.target()).cloned() {
-                let count = counts.entry(e.source()).or_insert(0);
-                *count += e.weight() * multiplier;
+/// A single line of the puzzle input, e.g.
+/// `light red bags contain 1 bright white bag, 2 muted yellow bags.`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub color: String,
+    /// the number and color of each kind of bag directly inside this bag
+    pub contents: Vec<(usize, String)>,
+}
+
+/// Why a rule could not be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleError {
+    /// rules must end with a '.'
+    MissingPeriod,
+    /// rules must be of the form `<bag> contain <contents>.`
+    MissingContain,
+    /// expected `<color> bag` or `<color> bags` but found something else
+    InvalidBag(String),
+    /// expected `<count> <color> bag(s)` but the count was not a positive integer
+    InvalidCount(String),
+    /// there was already a rule for bags of this color
+    DuplicateRule(String),
+}
+
+impl std::error::Error for RuleError {}
+
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RuleError::MissingPeriod => write!(f, "rule must end with a '.'"),
+            RuleError::MissingContain => write!(f, "rule must contain the word \"contain\""),
+            RuleError::InvalidBag(s) => {
+                write!(
+                    f,
+                    "expected \"<color> bag\" or \"<color> bags\", found {:?}",
+                    s
+                )
             }
+            RuleError::InvalidCount(s) => write!(f, "invalid number of bags: {:?}", s),
+            RuleError::DuplicateRule(color) => write!(f, "{} bags already have a rule", color),
+        }
+    }
+}
+
+/// A RuleError and the line of input (starting at 1) it happened on
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleParseError {
+    line: usize,
+    error: RuleError,
+}
+
+impl std::error::Error for RuleParseError {}
 
-            // dbg!(&counts
-            // .iter()
-            // .map(|(idx, count)| (graph.graph()[*idx].clone(), count))
-            // .collect::<HashMap<_, _>>());
+impl std::fmt::Display for RuleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+/// parses `<color> bag` or `<color> bags` and returns the color, which may be any number of words
+fn parse_bag(s: &str) -> Result<&str, RuleError> {
+    let color = s
+        .strip_suffix(" bags")
+        .or_else(|| s.strip_suffix(" bag"))
+        .ok_or_else(|| RuleError::InvalidBag(s.to_string()))?;
+    if color.split(' ').any(|word| word.is_empty()) {
+        return Err(RuleError::InvalidBag(s.to_string()));
+    }
+    Ok(color)
+}
+
+/// parses `<count> <color> bag(s)`
+fn parse_content(s: &str) -> Result<(usize, String), RuleError> {
+    let mut iter = s.splitn(2, ' ');
+    let count = iter.next().unwrap_or_default();
+    let count = count
+        .parse::<NonZeroUsize>()
+        .map_err(|_| RuleError::InvalidCount(count.to_string()))?
+        .get();
+    let color = parse_bag(iter.next().unwrap_or_default())?;
+    Ok((count, color.to_string()))
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().strip_suffix('.').ok_or(RuleError::MissingPeriod)?;
+        let mut iter = s.splitn(2, " contain ");
+        let color = parse_bag(iter.next().unwrap_or_default())?.to_string();
+        let contents = match iter.next().ok_or(RuleError::MissingContain)? {
+            "no other bags" => Vec::new(),
+            contents => contents
+                .split(", ")
+                .map(parse_content)
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(Rule { color, contents })
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} bags contain ", self.color)?;
+        if self.contents.is_empty() {
+            write!(f, "no other bags")?;
         }
+        for (i, (count, color)) in self.contents.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            let plural = if *count == 1 { "" } else { "s" };
+            write!(f, "{} {} bag{}", count, color, plural)?;
+        }
+        write!(f, ".")
+    }
+}
+
+impl BagGraph {
+    /// The rule for each bag, in the order the bags were added to the graph
+    /// bags that only appeared inside other bags contain no other bags
+    pub fn rules(&self) -> Vec<Rule> {
+        self.graph
+            .node_indices()
+            .map(|idx| {
+                let mut edges = self
+                    .graph
+                    .edges_directed(idx, Direction::Outgoing)
+                    .collect::<Vec<_>>();
+                // edges are stored most recent first, but the rule listed them first to last
+                edges.sort_by_key(|e| e.id());
+                Rule {
+                    color: self.graph[idx].clone(),
+                    contents: edges
+                        .into_iter()
+                        .map(|e| (*e.weight(), self.graph[e.target()].clone()))
+                        .collect(),
+                }
+            })
+            .collect()
     }
-    *counts.get(&idx).unwrap()
+}
+
+/// Writes out the rules that make up this graph, one per line, which can be parsed back into the same graph
+impl std::fmt::Display for BagGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for rule in self.rules() {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[aoc_generator(day7)]
+pub fn input_generator(input: &str) -> Result<BagGraph, RuleParseError> {
+    let mut rules = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |error| RuleParseError { line: i + 1, error };
+        let rule = line.parse::<Rule>().map_err(error)?;
+        if !seen.insert(rule.color.clone()) {
+            return Err(error(RuleError::DuplicateRule(rule.color)));
+        }
+        rules.push(rule);
+    }
+
+    // add every bag with a rule first so that the graph's bags are in the same order as the rules
+    let mut bag_graph = BagGraph::new();
+    for rule in &rules {
+        bag_graph.add_bag(rule.color.clone());
+    }
+    for rule in rules {
+        for (count, contained_color) in rule.contents {
+            bag_graph.contains_bag(rule.color.clone(), count, contained_color);
+        }
+    }
+
+    Ok(bag_graph)
+}
+
+/// Counts the bags that can eventually contain a shiny gold bag (not the bags inside it!)
+#[aoc(day7, part1)]
+pub fn part1(graph: &BagGraph) -> Result<usize, BagError> {
+    graph.containers_of(SHINY_GOLD).map(|bags| bags.len())
+}
+
+#[aoc(day7, part2)]
+pub fn part2(graph: &BagGraph) -> Result<usize, BagError> {
+    graph.contents_count(SHINY_GOLD)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const TEST_INPUT_1: &'static str = "\
+    const TEST_INPUT_1: &str = "\
 light red bags contain 1 bright white bag, 2 muted yellow bags.
 dark orange bags contain 3 bright white bags, 4 muted yellow bags.
 bright white bags contain 1 shiny gold bag.
@@ -154,7 +522,7 @@ vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
 
-    const TEST_INPUT_2: &'static str = "\
+    const TEST_INPUT_2: &str = "\
 shiny gold bags contain 2 dark red bags.
 dark red bags contain 2 dark orange bags.
 dark orange bags contain 2 dark yellow bags.
@@ -165,13 +533,336 @@ dark violet bags contain no other bags.";
 
     #[test]
     fn part1_works() {
-        assert_eq!(4, part1(&input_generator(TEST_INPUT_1)));
+        assert_eq!(4, part1(&input_generator(TEST_INPUT_1).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn part1_counts_containers_not_contents() {
+        // shiny gold can be found inside 1 bag but has 3 bags inside it,
+        // in TEST_INPUT_1 these are both 4 so searching in the wrong direction still passes
+        let graph = input_generator(
+            "\
+dark red bags contain 2 shiny gold bags.
+shiny gold bags contain 1 dark olive bag, 1 vibrant plum bag.
+dark olive bags contain 1 faded blue bag.
+vibrant plum bags contain no other bags.
+faded blue bags contain no other bags.",
+        )
+        .unwrap();
+        assert_eq!(1, part1(&graph).unwrap());
+        assert_eq!(3, graph.contents_of("shiny gold").unwrap().len());
+
+        assert_eq!(0, part1(&input_generator(TEST_INPUT_2).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_works() {
-        assert_eq!(126, part2(&input_generator(TEST_INPUT_2)));
+        assert_eq!(126, part2(&input_generator(TEST_INPUT_2).unwrap()).unwrap());
+
+        assert_eq!(32, part2(&input_generator(TEST_INPUT_1).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn queries_work() {
+        let graph = input_generator(TEST_INPUT_1).unwrap();
+
+        assert_eq!(
+            vec!["bright white", "dark orange", "light red", "muted yellow"],
+            graph.containers_of("shiny gold").unwrap()
+        );
+        assert_eq!(
+            vec!["dark olive", "dotted black", "faded blue", "vibrant plum"],
+            graph.contents_of("shiny gold").unwrap()
+        );
+        assert!(graph.containers_of("light red").unwrap().is_empty());
+        assert!(graph.contents_of("faded blue").unwrap().is_empty());
+
+        assert_eq!(0, graph.contents_count("faded blue").unwrap());
+        assert_eq!(7, graph.contents_count("dark olive").unwrap());
+        assert_eq!(11, graph.contents_count("vibrant plum").unwrap());
+    }
+
+    #[test]
+    fn path_between_works() {
+        let graph = input_generator(TEST_INPUT_1).unwrap();
+
+        assert_eq!(
+            Some(vec!["bright white", "shiny gold", "dark olive"]),
+            graph.path_between("bright white", "dark olive").unwrap()
+        );
+        assert_eq!(
+            Some(vec!["light red", "muted yellow", "faded blue"]),
+            graph.path_between("light red", "faded blue").unwrap()
+        );
+        assert_eq!(None, graph.path_between("dark olive", "light red").unwrap());
+    }
+
+    #[test]
+    fn contents_counts_are_remembered() {
+        let mut graph = input_generator(TEST_INPUT_1).unwrap();
+
+        assert_eq!(7, graph.contents_count("dark olive").unwrap());
+        assert_eq!(3, graph.contents_counts.borrow().len());
+
+        assert_eq!(32, graph.contents_count("shiny gold").unwrap());
+        assert_eq!(5, graph.contents_counts.borrow().len());
+
+        // adding a rule changes the answers
+        graph.contains_bag("dark olive".into(), 1, "shiny red".into());
+        assert!(graph.contents_counts.borrow().is_empty());
+        assert_eq!(8, graph.contents_count("dark olive").unwrap());
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let graph = input_generator(
+            "\
+shiny gold bags contain 1 dark red bag.
+dark red bags contain 2 dark orange bags, 1 faded blue bag.
+dark orange bags contain 3 shiny gold bags.
+faded blue bags contain no other bags.
+light red bags contain 1 faded blue bag.",
+        )
+        .unwrap();
+
+        let cycle = vec![
+            "dark red".to_string(),
+            "dark orange".to_string(),
+            "shiny gold".to_string(),
+        ];
+        match graph.contents_count("dark red") {
+            Err(BagError::Cycle(colors)) => {
+                // any bag in the cycle could be reported first
+                let start = colors.iter().position(|c| c == "dark red").unwrap();
+                assert_eq!(cycle, [&colors[start..], &colors[..start]].concat());
+            }
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        assert_eq!(
+            "bags contain themselves: dark red -> dark orange -> shiny gold -> dark red",
+            BagError::Cycle(cycle).to_string()
+        );
+        assert_eq!(
+            "bags contain themselves: ",
+            BagError::Cycle(Vec::new()).to_string()
+        );
+
+        // bags that don't lead into the cycle can still be counted
+        assert_eq!(1, graph.contents_count("light red").unwrap());
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let input = (0..10)
+            .map(|n| format!("dark c{} bags contain 1000 dark c{} bags.", n, n + 1))
+            .chain(std::iter::once(
+                "dark c10 bags contain no other bags.".into(),
+            ))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let graph = input_generator(&input).unwrap();
+
+        assert_eq!(1000, graph.contents_count("dark c9").unwrap());
+        assert!(matches!(
+            graph.contents_count("dark c0"),
+            Err(BagError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn to_dot_works() {
+        let graph = input_generator(
+            "\
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 2 dark olive bags.
+dark olive bags contain no other bags.
+faded blue bags contain 3 dark olive bags.",
+        )
+        .unwrap();
+
+        assert_eq!(
+            r#"digraph {
+    0 [ label = "bright white" ]
+    1 [ label = "shiny gold" ]
+    2 [ label = "dark olive" ]
+    3 [ label = "faded blue" ]
+    0 -> 1 [ label = "1" ]
+    1 -> 2 [ label = "2" ]
+    3 -> 2 [ label = "3" ]
+}
+"#,
+            graph.to_dot(None).unwrap()
+        );
+
+        assert_eq!(
+            r#"digraph {
+    0 [ label = "bright white" color = blue ]
+    1 [ label = "shiny gold" style = filled fillcolor = gold ]
+    2 [ label = "dark olive" color = red ]
+    3 [ label = "faded blue" ]
+    0 -> 1 [ label = "1" color = blue ]
+    1 -> 2 [ label = "2" color = red ]
+    3 -> 2 [ label = "3" ]
+}
+"#,
+            graph.to_dot(Some("shiny gold")).unwrap()
+        );
+    }
+
+    #[test]
+    fn subgraph_works() {
+        let graph = input_generator(TEST_INPUT_1).unwrap();
+
+        let subgraph = graph.subgraph("dark olive").unwrap();
+        assert_eq!(
+            vec![
+                "bright white",
+                "dark orange",
+                "light red",
+                "muted yellow",
+                "shiny gold"
+            ],
+            subgraph.containers_of("dark olive").unwrap()
+        );
+        assert_eq!(
+            vec!["dotted black", "faded blue"],
+            subgraph.contents_of("dark olive").unwrap()
+        );
+        // light red only gets to dark olive via shiny gold, so shiny gold's other contents are left out
+        assert!(subgraph.lookup_node_index("vibrant plum").is_none());
+
+        let subgraph = graph.subgraph("faded blue").unwrap();
+        assert_eq!(0, subgraph.contents_count("faded blue").unwrap());
+        assert_eq!(
+            graph.graph().node_count(),
+            subgraph.graph().node_count() + 1
+        );
+    }
+
+    #[test]
+    fn rule_parsing_works() {
+        assert_eq!(
+            Rule {
+                color: "light red".to_string(),
+                contents: vec![
+                    (1, "bright white".to_string()),
+                    (2, "muted yellow".to_string())
+                ],
+            },
+            "light red bags contain 1 bright white bag, 2 muted yellow bags."
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            Rule {
+                color: "very pale sky blue".to_string(),
+                contents: vec![(3, "gold".to_string()), (1, "deep shiny red".to_string())],
+            },
+            "very pale sky blue bag contain 3 gold bag, 1 deep shiny red bags."
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            Rule {
+                color: "faded blue".to_string(),
+                contents: vec![],
+            },
+            "faded blue bags contain no other bags.".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn rule_errors() {
+        let error = |input: &str| input.parse::<Rule>().unwrap_err();
+
+        assert_eq!(
+            RuleError::MissingPeriod,
+            error("faded blue bags contain no other bags")
+        );
+        assert_eq!(
+            RuleError::MissingContain,
+            error("faded blue bags hold 2 shiny gold bags.")
+        );
+        assert_eq!(
+            RuleError::InvalidBag("faded blue".to_string()),
+            error("faded blue contain no other bags.")
+        );
+        assert_eq!(
+            RuleError::InvalidBag("shiny  gold bags".to_string()),
+            error("faded blue bags contain 1 shiny  gold bags.")
+        );
+        assert_eq!(
+            RuleError::InvalidCount("two".to_string()),
+            error("faded blue bags contain two shiny gold bags.")
+        );
+        assert_eq!(
+            RuleError::InvalidCount("-1".to_string()),
+            error("faded blue bags contain -1 shiny gold bags.")
+        );
+        assert_eq!(
+            RuleError::InvalidCount("0".to_string()),
+            error("faded blue bags contain 0 shiny gold bags.")
+        );
+    }
+
+    #[test]
+    fn input_errors_have_line_numbers() {
+        let input = "\
+light red bags contain 1 bright white bag.
+
+bright white bags contain 1 shiny gold bag
+shiny gold bags contain no other bags.";
+        let error = input_generator(input).err().unwrap();
+        assert_eq!(
+            RuleParseError {
+                line: 3,
+                error: RuleError::MissingPeriod
+            },
+            error
+        );
+        assert_eq!("line 3: rule must end with a '.'", error.to_string());
+
+        let input = "\
+light red bags contain 1 bright white bag.
+light red bags contain no other bags.";
+        assert_eq!(
+            RuleParseError {
+                line: 2,
+                error: RuleError::DuplicateRule("light red".to_string())
+            },
+            input_generator(input).err().unwrap()
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let graph = input_generator(TEST_INPUT_1).unwrap();
+        assert_eq!(format!("{}\n", TEST_INPUT_1), graph.to_string());
+
+        // bags that only appear inside other bags get a rule of their own
+        let graph = input_generator("dull red bag contain 1 shiny gold bag.").unwrap();
+        assert_eq!(
+            "\
+dull red bags contain 1 shiny gold bag.
+shiny gold bags contain no other bags.
+",
+            graph.to_string()
+        );
+    }
+
+    #[test]
+    fn unknown_colors() {
+        let graph = input_generator(TEST_INPUT_1).unwrap();
+        let unknown = BagError::UnknownColor("plaid".to_string());
 
-        assert_eq!(32, part2(&input_generator(TEST_INPUT_1)));
+        assert_eq!(Err(unknown.clone()), graph.containers_of("plaid"));
+        assert_eq!(Err(unknown.clone()), graph.contents_of("plaid"));
+        assert_eq!(Err(unknown.clone()), graph.contents_count("plaid"));
+        assert_eq!(
+            Err(unknown.clone()),
+            graph.path_between("shiny gold", "plaid")
+        );
+        assert_eq!(Err(unknown), graph.to_dot(Some("plaid")));
+        assert!(graph.subgraph("plaid").is_err());
     }
 }
@@ -1,247 +1,253 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use parse_display::FromStr;
 
-use std::collections::HashSet;
+use crate::console::analysis::{flow, halting, Flow};
+use crate::console::{Instruction, InstructionSet, Machine, Program, ProgramParseError, Status};
+use crate::console::{JMP, NOP};
 
-/*
-#[derive(FromStr, PartialEq, Debug)]
-#[display("acc {amount}")]
-struct Acc {
-    amount: isize,
+/// This will output a Program
+#[aoc_generator(day8)]
+pub fn input_generator(input: &str) -> Result<Program, ProgramParseError> {
+    InstructionSet::handheld().parse_program(input)
 }
 
-#[derive(FromStr, PartialEq, Debug)]
-#[display("jmp +{offset}")]
-struct Jump {
-    offset: isize,
-}
-*/
-
-#[derive(Clone, FromStr, PartialEq, Debug)]
-pub enum Instruction {
-    #[from_str(regex = "acc (?P<0>[-+][0-9]+)")]
-    Acc(i32),
-    #[from_str(regex = "jmp (?P<0>[-+][0-9]+)")]
-    Jump(i32),
-    #[from_str(regex = "nop (?P<0>[-+][0-9]+)")]
-    Noop(i32),
+/// The value of the accumulator immediately before any instruction is run a second time
+#[aoc(day8, part1)]
+pub fn part1(program: &Program) -> Option<i64> {
+    let mut machine = Machine::new();
+    match machine.run(program) {
+        Status::Looped => Some(machine.accumulator()),
+        _ => None,
+    }
 }
 
-/// visited records every unique instruction_pointer (i.e. Program index) that has already been executed
-/// in order to detect infinite loops at runtime
-#[derive(Default, Debug, PartialEq)]
-pub struct Machine {
-    instruction_pointer: usize,
-    accumulator: i32,
-    visited: HashSet<usize>,
+/// The value of the accumulator after the program halts,
+/// once exactly one jmp has been swapped for a nop or one nop for a jmp
+#[aoc(day8, part2)]
+pub fn part2(program: &Program) -> Option<i64> {
+    let patched = repair(program)?.apply(program);
+    let mut machine = Machine::new();
+    match machine.run(&patched) {
+        Status::Halted => Some(machine.accumulator()),
+        _ => None,
+    }
 }
 
-/// Program stores instructions and state
+/// A single instruction swapped to make a program halt
 #[derive(Clone, Debug, PartialEq)]
-pub struct Program {
-    instructions: Vec<Instruction>,
-    state: ProgramState,
+pub struct Repair {
+    pub address: usize,
+    pub original: Instruction,
+    pub replacement: Instruction,
 }
 
-/// Records the most recent shuffle made to the Program
-/// for the purposes of brute forcing Program alterations
-#[derive(Debug, Clone, PartialEq)]
-pub struct ProgramState {
-    // the index of the next instruction to change
-    next: usize,
-    // the index of the most recently changed instruction (if any)
-    prev: Option<usize>,
-    repeat: bool,
-}
-
-impl Program {
-    fn get(&self, index: usize) -> Option<&Instruction> {
-        self.instructions.get(index)
-    }
-
-    // swaps Jumps for Noops, and maintains only 1 swap at a time
-    // self.state.repeat is true if we are running shuffle recursively to skip Instructions that shouldn't be shuffled
-    // i.e. Acc
-    // it is set by the shuffle function where necessary
-    fn shuffle(&mut self) {
-        // swap back previous
-        if self.state.repeat == false {
-            if let Some(prev) = self.state.prev {
-                match self.instructions.get(prev) {
-                    None => unreachable!(),
-                    Some(Instruction::Jump(x)) => self.instructions[prev] = Instruction::Noop(*x),
-                    Some(Instruction::Noop(x)) => self.instructions[prev] = Instruction::Jump(*x),
-                    Some(Instruction::Acc(_)) => unreachable!(),
-                }
-            }
-        }
-        // swap current
-        match self.instructions.get(self.state.next) {
-            None => panic!("we tried all of the shuffles and now we don't know what to do!"),
-            Some(Instruction::Jump(x)) => {
-                self.state.prev = Some(self.state.next);
-                self.instructions[self.state.next] = Instruction::Noop(*x);
-            }
-            Some(Instruction::Noop(x)) => {
-                self.state.prev = Some(self.state.next);
-                self.instructions[self.state.next] = Instruction::Jump(*x);
-            }
-            Some(Instruction::Acc(_)) => {
-                self.state.repeat = true;
-                self.state.next += 1;
-                self.shuffle()
-            }
-        }
-        self.state.next += 1;
-        self.state.repeat = false;
+impl Repair {
+    /// a copy of program with the repair made
+    pub fn apply(&self, program: &Program) -> Program {
+        let mut program = program.clone();
+        program.replace(self.address, self.replacement.clone());
+        program
     }
 }
 
-impl Machine {
-    fn new() -> Machine {
-        Machine::default()
-    }
-
-    /// Step through one instruction of the program at the instruction_pointer
-    /// returns Some(x) if the instruction_pointer was within the bounds of the Program
-    /// where x is the value of the accumulator after running that instruction
-    /// returns None otherwise indicating that the program is finished.
-    fn step(&mut self, program: &Program) -> Option<i32> {
-        if let Some(next) = program.get(self.instruction_pointer) {
-            if self.visited.insert(self.instruction_pointer) {
-                &self.execute(next);
-                Some(self.accumulator)
-            } else {
-                // instruction has been run before...
-                // infinite loop detected - stop
-                None
+/// Finds the one jmp or nop that can be swapped so that the program halts, in linear time
+///
+/// Only instructions the program actually runs can make a difference, and once one of them is swapped
+/// the program carries on as normal from wherever the swapped instruction leads.
+/// So we work out which instructions lead to the program halting without any swaps,
+/// and then look along the original path for the first swap that leads into one of them.
+/// (Instructions that halt can never lead back into the original path, because that path loops forever)
+pub fn repair(program: &Program) -> Option<Repair> {
+    let halts = halting(program);
+    let leads_to_halt = |flow| match flow {
+        Flow::To(next) => halts[next],
+        Flow::Halt => true,
+        Flow::Fault | Flow::Unknown => false,
+    };
+
+    let mut visited = vec![false; program.len()];
+    let mut address = 0;
+    while address < program.len() && !visited[address] {
+        visited[address] = true;
+        let original = &program.instructions()[address];
+        if let Some(replacement) = swap_jmp_nop(original) {
+            if leads_to_halt(flow(&replacement, address, program.len())) {
+                return Some(Repair {
+                    address,
+                    original: original.clone(),
+                    replacement,
+                });
             }
-        } else {
-            None
         }
-    }
-
-    /// resets the machine and runs through the program until it finishes or finds an infinite loop
-    /// returns Ok(accumulator) if finishes, returns Err(accumulator) if it detects an infinite loop.
-    fn run(&mut self, program: &Program) -> Result<i32, i32> {
-        self.instruction_pointer = 0;
-        self.accumulator = 0;
-        self.visited = HashSet::new();
-        while let Some(next) = program.get(self.instruction_pointer) {
-            if self.visited.insert(self.instruction_pointer) {
-                &self.execute(next);
-            } else {
-                // instruction has been run before...
-                // infinite loop detected - stop
-                return Err(self.accumulator);
-            }
-        }
-        Ok(self.accumulator)
-    }
-
-    /// Mutates self in order to do the instruction
-    /// panics if the instruction pointer is set to a negative value by too large a negative jump
-    fn execute(&mut self, instruction: &Instruction) {
-        match instruction {
-            Instruction::Acc(x) => {
-                self.accumulator += x;
-                self.instruction_pointer += 1;
-            }
-            Instruction::Jump(x) => {
-                self.instruction_pointer = (self.instruction_pointer as i32 + x) as usize;
-            }
-            Instruction::Noop(_) => {
-                self.instruction_pointer += 1;
-            }
+        match flow(original, address, program.len()) {
+            Flow::To(next) => address = next,
+            // the program doesn't loop, so there's nothing to repair
+            Flow::Halt | Flow::Fault => return None,
+            // a register jump can't be followed without running the program
+            Flow::Unknown => return None,
         }
     }
+    None
 }
 
-/// This will output a Program
-#[aoc_generator(day8)]
-pub fn input_generator(input: &str) -> Program {
-    Program {
-        instructions: input
-            .lines()
-            .map(|line| line.parse::<Instruction>())
-            .collect::<Result<_, _>>()
-            .unwrap(),
-        state: ProgramState {
-            next: 0,
-            prev: None,
-            repeat: false,
-        },
-    }
-}
-
-#[aoc(day8, part1)]
-pub fn part1(program: &Program) -> Option<i32> {
-    let mut machine = Machine::new();
-
-    let mut answer = None;
-
-    while let Some(acc) = &machine.step(program) {
-        println!("{}", acc);
-        answer = Some(*acc)
-    }
-    println!("finished");
-    answer
-}
-
-#[aoc(day8, part2)]
-pub fn part2(program: &Program) -> Option<i32> {
-    let mut machine = Machine::new();
-    let mut program = (*program).clone();
-
-    let mut answer = Err(0);
-
-    while answer.is_err() {
-        program.shuffle();
-        answer = machine.run(&program);
-    }
-    answer.ok()
+/// swaps a jmp for a nop or a nop for a jmp, other instructions can't be swapped
+fn swap_jmp_nop(instruction: &Instruction) -> Option<Instruction> {
+    let opcode = if instruction.opcode() == &JMP {
+        &NOP
+    } else if instruction.opcode() == &NOP {
+        &JMP
+    } else {
+        return None;
+    };
+    // jmp and nop have the same arity, so the operands still fit
+    Instruction::new(opcode, instruction.operands().to_vec()).ok()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const TEST_PROGRAM: &'static str = "\
-nop +0
-acc +1
-jmp +4
-acc +3
-jmp -3
-acc -99
-acc +1
-jmp -4
-acc +6";
+    use crate::console::{test_program, Operand, ACC, TEST_PROGRAM};
+    use crate::test_util::Lcg;
 
     #[test]
     fn program_parsing_works() {
+        let instruction = |opcode, x| Instruction::new(opcode, vec![Operand::Value(x)]).unwrap();
         assert_eq!(
             vec![
-                Instruction::Noop(0),
-                Instruction::Acc(1),
-                Instruction::Jump(4),
-                Instruction::Acc(3),
-                Instruction::Jump(-3),
-                Instruction::Acc(-99),
-                Instruction::Acc(1),
-                Instruction::Jump(-4),
-                Instruction::Acc(6),
+                instruction(&NOP, 0),
+                instruction(&ACC, 1),
+                instruction(&JMP, 4),
+                instruction(&ACC, 3),
+                instruction(&JMP, -3),
+                instruction(&ACC, -99),
+                instruction(&ACC, 1),
+                instruction(&JMP, -4),
+                instruction(&ACC, 6),
             ],
-            input_generator(TEST_PROGRAM).instructions
+            input_generator(TEST_PROGRAM).unwrap().instructions()
         );
     }
 
     #[test]
     fn part1_works() {
-        assert_eq!(5, part1(&input_generator(TEST_PROGRAM)).unwrap());
+        assert_eq!(5, part1(&input_generator(TEST_PROGRAM).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_works() {
-        assert_eq!(8, part2(&input_generator(TEST_PROGRAM)).unwrap());
+        assert_eq!(8, part2(&input_generator(TEST_PROGRAM).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn repair_works() {
+        let program = input_generator(TEST_PROGRAM).unwrap();
+        let fix = repair(&program).unwrap();
+
+        assert_eq!(7, fix.address);
+        assert_eq!("jmp -4", fix.original.to_string());
+        assert_eq!("nop -4", fix.replacement.to_string());
+        assert_eq!(
+            TEST_PROGRAM.replace("jmp -4", "nop -4") + "\n",
+            fix.apply(&program).to_string()
+        );
+    }
+
+    #[test]
+    fn repair_follows_the_original_path() {
+        // swapping either instruction works, the first one run is the one found
+        let program = input_generator("nop +3\njmp +0\nacc +1").unwrap();
+        let fix = repair(&program).unwrap();
+        assert_eq!(0, fix.address);
+
+        // swapping the nop at 1 would halt, but it is never run so only swapping the jmp at 3 helps
+        let program = input_generator("jmp +2\nnop +5\nacc +1\njmp -1\nacc +2").unwrap();
+        let fix = repair(&program).unwrap();
+        assert_eq!(3, fix.address);
+        assert_eq!(Some(3), part2(&program));
+    }
+
+    #[test]
+    fn repair_avoids_jumping_out_of_bounds() {
+        // swapping the nop at 1 would jump way past the end, which is a fault rather than halting
+        let program = input_generator("nop +0\nnop +5\njmp +0\nacc +1").unwrap();
+        let fix = repair(&program).unwrap();
+        assert_eq!(2, fix.address);
+        assert_eq!(Some(1), part2(&program));
+
+        // negative jumps too
+        let program = input_generator("nop -1\njmp +0").unwrap();
+        let fix = repair(&program).unwrap();
+        assert_eq!(1, fix.address);
+    }
+
+    #[test]
+    fn repair_can_fail() {
+        // swapping either jmp that is run leads into another infinite loop
+        let program = input_generator("jmp +2\njmp +0\njmp -2\njmp +0").unwrap();
+        assert_eq!(None, repair(&program));
+        assert_eq!(None, part2(&program));
+    }
+
+    /// A program with `size` instructions that runs straight through and then jumps back to the start,
+    /// only the final jmp can be swapped to make it halt
+    fn synthetic_program(size: usize) -> Program {
+        let mut lcg = Lcg::new(0x2020_0808);
+        let mut source = String::new();
+        for _ in 0..size - 1 {
+            let random = lcg.random();
+            let value = random as i64 % 100 - 50;
+            let line = match random >> 29 {
+                0 | 1 => format!("acc {:+}\n", value),
+                2 => format!("nop {:+}\n", value),
+                _ => "jmp +1\n".to_string(),
+            };
+            source.push_str(&line);
+        }
+        source.push_str(&format!("jmp -{}", size - 1));
+        input_generator(&source).unwrap()
+    }
+
+    #[test]
+    #[ignore]
+    fn repair_large_programs() {
+        use std::time::Instant;
+
+        let program = synthetic_program(100_000);
+
+        let start = Instant::now();
+        let repair = repair(&program).unwrap();
+        println!(
+            "repair {} instructions: {:?}",
+            program.len(),
+            start.elapsed()
+        );
+        let mut machine = Machine::new();
+        assert_eq!(&Status::Halted, machine.run(&repair.apply(&program)));
+
+        // a machine's allocations matter most when it runs lots of short programs
+        const RUNS: usize = 100_000;
+        let program = test_program();
+
+        let start = Instant::now();
+        let mut machine = Machine::new();
+        for _ in 0..RUNS {
+            // run resets the machine first
+            machine.run(&program);
+        }
+        println!(
+            "{} short runs reusing a machine: {:?}",
+            RUNS,
+            start.elapsed()
+        );
+
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            Machine::new().run(&program);
+        }
+        println!(
+            "{} short runs with a new machine each: {:?}",
+            RUNS,
+            start.elapsed()
+        );
     }
 }
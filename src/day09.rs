@@ -1,162 +1,440 @@
-use aoc_runner_derive::aoc;
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::num::ParseIntError;
+use std::ops::Range;
 
+use aoc_runner_derive::{aoc, aoc_generator};
+
+use crate::config::{split_header, ConfigError};
+
+/// The preamble length used by the real input, an input can set its own with a `# preamble = 5` header
+pub const PREAMBLE_LENGTH: usize = 25;
+
+/// The XMAS cipher, which moves through these states in order:
+/// first it reads the preamble, then it scans numbers until one isn't valid, which is the weakness
+/// Only an Xmas with a weakness can be cracked, so the methods for each state are only available in that state
+/// numbers is every number seen so far, they are all needed to crack the code
 #[derive(Debug, Clone, PartialEq)]
-pub struct Xmas {
+pub struct Xmas<State> {
     numbers: Vec<u64>,
-    cursor: usize,
-    preamble_length: usize,
-    weakness: Option<u64>,
-    encryption_weakness: Option<u64>,
+    window: Window,
+    state: State,
+}
+
+/// The last few numbers, which the next number must be the sum of two of
+/// counts is a multiset of the numbers in the window, kept up to date as the window moves along,
+/// so that checking whether a pair exists only needs one lookup per number in the window
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    numbers: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+    length: usize,
+}
+
+impl Window {
+    fn new(length: usize) -> Self {
+        Window {
+            numbers: VecDeque::with_capacity(length + 1),
+            counts: HashMap::new(),
+            length,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.numbers.len() == self.length
+    }
+
+    /// adds the next number, moving the window along once it is full
+    fn push(&mut self, number: u64) {
+        *self.counts.entry(number).or_insert(0) += 1;
+        self.numbers.push_back(number);
+
+        if self.numbers.len() > self.length {
+            // the oldest number drops out of the window
+            if let Some(oldest) = self.numbers.pop_front() {
+                if let Some(count) = self.counts.get_mut(&oldest) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.counts.remove(&oldest);
+                    }
+                }
+            }
+        }
+    }
+
+    /// checks that two different numbers in the window add up to the target
+    fn validate(&self, target: u64) -> Result<(u64, u64), u64> {
+        for &have in &self.numbers {
+            if let Some(need) = target.checked_sub(have) {
+                // there is no way to make the target without using the same number twice (which isn't allowed)
+                if need != have && self.counts.contains_key(&need) {
+                    return Ok((have, need));
+                }
+            }
+        }
+        // found the weakness: it had no preceeding pair that added to it
+        Err(target)
+    }
+}
+
+/// Waiting to read the preamble
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preamble;
+
+/// Checking each number is the sum of two of the numbers before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scanning;
+
+/// Found a number that isn't the sum of two of the numbers before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weakness(u64);
+
+/// The result of scanning one number
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scan {
+    Valid(Xmas<Scanning>),
+    Invalid(Xmas<Weakness>),
+}
+
+/// A contiguous range of at least two numbers that add up to the weakness
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptionWeakness {
+    /// the indexes of the numbers in the range
+    pub range: Range<usize>,
+    /// the smallest plus the largest number in the range, or None if that doesn't fit in a u64
+    pub value: Option<u64>,
 }
 
-impl Xmas {
-    fn new(preamble_length: usize) -> Self {
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmasError {
+    Config(ConfigError),
+    /// a line of input isn't a positive integer
+    InvalidNumber {
+        line: usize,
+        error: ParseIntError,
+    },
+    /// the input ran out before the end of the preamble
+    ShortPreamble {
+        expected: usize,
+        found: usize,
+    },
+    /// every number is valid
+    NoWeakness,
+    /// no contiguous range adds up to the weakness
+    NoEncryptionWeakness(u64),
+    /// the smallest plus the largest number in the encryption weakness doesn't fit in a u64
+    EncryptionWeaknessOverflow(Range<usize>),
+}
+
+impl std::error::Error for XmasError {}
+
+impl From<ConfigError> for XmasError {
+    fn from(error: ConfigError) -> Self {
+        XmasError::Config(error)
+    }
+}
+
+impl std::fmt::Display for XmasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            XmasError::Config(error) => error.fmt(f),
+            XmasError::InvalidNumber { line, error } => {
+                write!(f, "invalid number on line {}: {}", line, error)
+            }
+            XmasError::ShortPreamble { expected, found } => write!(
+                f,
+                "preamble needs {} numbers but only found {}",
+                expected, found
+            ),
+            XmasError::NoWeakness => write!(f, "every number is valid, there is no weakness"),
+            XmasError::NoEncryptionWeakness(weakness) => {
+                write!(
+                    f,
+                    "no contiguous range adds up to the weakness {}",
+                    weakness
+                )
+            }
+            XmasError::EncryptionWeaknessOverflow(range) => write!(
+                f,
+                "the smallest plus the largest number in {:?} is too big",
+                range
+            ),
+        }
+    }
+}
+
+impl Xmas<Preamble> {
+    pub fn new(preamble_length: usize) -> Self {
         Xmas {
             numbers: Vec::new(),
-            cursor: 0,
-            preamble_length,
-            weakness: None,
-            encryption_weakness: None,
+            window: Window::new(preamble_length),
+            state: Preamble,
+        }
+    }
+
+    /// reads exactly enough numbers for the preamble, ready to start scanning
+    pub fn preamble(
+        mut self,
+        numbers: impl IntoIterator<Item = u64>,
+    ) -> Result<Xmas<Scanning>, XmasError> {
+        for number in numbers.into_iter().take(self.window.length) {
+            self.push(number);
+        }
+        if !self.window.is_full() {
+            return Err(XmasError::ShortPreamble {
+                expected: self.window.length,
+                found: self.numbers.len(),
+            });
+        }
+        Ok(self.into_state(Scanning))
+    }
+}
+
+impl<State> Xmas<State> {
+    fn into_state<Next>(self, state: Next) -> Xmas<Next> {
+        Xmas {
+            numbers: self.numbers,
+            window: self.window,
+            state,
         }
     }
 
-    /// look at the next number and return it if it is a weakness
-    /// A weakness can only be detected if we have finished the preamble
-    fn process(&mut self, number: u64) -> Option<u64> {
+    fn push(&mut self, number: u64) {
+        self.window.push(number);
         self.numbers.push(number);
+    }
 
-        if self.numbers.len() > self.preamble_length {
-            match self.validate() {
-                Err(weakness) => {
-                    //println!("{} is a weakness", weakness);
-                    self.weakness = Some(weakness);
-                    self.cursor += 1;
-                }
-                Ok(_) => {
-                    //Ok((a, b)) => {
-                    //println!("Ok: {} + {} = {}", a, b, self.numbers.last().expect("numbers must not be empty"));
-                    self.cursor += 1;
-                }
-            };
+    fn validate(&self, target: u64) -> Result<(u64, u64), u64> {
+        self.window.validate(target)
+    }
+}
+
+impl Xmas<Scanning> {
+    /// look at the next number, and stop scanning if it is a weakness
+    pub fn process(mut self, number: u64) -> Scan {
+        let valid = self.validate(number);
+        self.push(number);
+        match valid {
+            Ok(_) => Scan::Valid(self),
+            Err(weakness) => Scan::Invalid(self.into_state(Weakness(weakness))),
         }
+    }
 
-        self.weakness
+    /// processes numbers until one of them is a weakness
+    pub fn find_weakness(
+        self,
+        numbers: impl IntoIterator<Item = u64>,
+    ) -> Result<Xmas<Weakness>, XmasError> {
+        let mut xmas = self;
+        for number in numbers {
+            match xmas.process(number) {
+                Scan::Valid(scanning) => xmas = scanning,
+                Scan::Invalid(weak) => return Ok(weak),
+            }
+        }
+        Err(XmasError::NoWeakness)
     }
+}
 
-    fn validate(&self) -> Result<(u64, u64), u64> {
-        let target = self.numbers.last().expect("numbers must not be empty");
-        let last_index = self.numbers.len() - 1;
+impl Xmas<Weakness> {
+    /// the first number that isn't the sum of two of the numbers before it
+    pub fn weakness(&self) -> u64 {
+        self.state.0
+    }
 
-        for i in self.cursor..last_index {
-            if let Some(need) = target.checked_sub(self.numbers[i]) {
-                //println!("searching... target: {}, have: {}, need: {}", target, self.numbers[i], need);
-                if need == self.numbers[i] {
-                    // exit early because now there is no way to make the target without using the same number twice (which isn't allowed)
-                    continue;
-                }
-                for j in (i + 1)..last_index {
-                    if self.numbers[j] == need {
-                        return Ok((self.numbers[i], self.numbers[j]));
-                    }
-                }
-            } else {
-                //println!("skipping... target: {}, have: {}", target, self.numbers[i]);
+    /// Cracks the code by finding a contiguous range of the numbers up to the weakness that add up to it
+    pub fn crack(&self) -> Result<EncryptionWeakness, XmasError> {
+        contiguous_sum(&self.numbers, self.weakness())
+            .ok_or_else(|| XmasError::NoEncryptionWeakness(self.weakness()))
+    }
+}
+
+/// A number that isn't the sum of two of the numbers before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidNumber {
+    /// the position of the number in the input, starting at 0
+    pub index: usize,
+    pub number: u64,
+}
+
+#[derive(Debug)]
+pub enum ScanError {
+    Io(std::io::Error),
+    /// a line (starting at 1) that isn't a positive integer
+    InvalidNumber {
+        line: usize,
+        error: ParseIntError,
+    },
+}
+
+impl std::error::Error for ScanError {}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ScanError::Io(error) => error.fmt(f),
+            ScanError::InvalidNumber { line, error } => {
+                write!(f, "invalid number on line {}: {}", line, error)
             }
         }
-        // found the weakness: it had no preceeding pair that added to it
-        Err(*target)
-    }
-
-    /// Attempts to crack the code by using the stored weakness
-    /// Before cracking, find the weakness by processing input one at a time in order
-    /// Returns None if no weakness is stored or no further weakness can be found using the stored weakness
-    /// TODO: simple state machine to prevent misuse of the Xmas struct at compilation time
-    pub fn crack(&mut self) -> Option<u64> {
-        if self.encryption_weakness.is_some() {
-            return self.encryption_weakness;
-        };
-
-        if let Some(weakness) = self.weakness {
-            // outer loop
-            for i in 0..self.numbers.len() {
-                let mut cursor = i;
-                let mut sum = 0;
-                let mut smallest = None;
-                let mut largest = None;
-                // "contiguous sum" loop
-                while sum < weakness {
-                    if let Some(next) = self.numbers.get(cursor) {
-                        if smallest.is_none() || next < smallest.unwrap() {
-                            smallest = Some(next);
-                        }
-
-                        if largest.is_none() || next > largest.unwrap() {
-                            largest = Some(next);
-                        }
-
-                        sum += next;
-                        cursor += 1;
-                        if cursor - i >= 2 {
-                            if sum == weakness {
-                                self.encryption_weakness = Some(
-                                    smallest.expect("must have a smallest by now")
-                                        + largest.expect("must have a largest by now"),
-                                );
-                                return self.encryption_weakness;
-                            }
-                        }
-                    } else {
-                        // println!("Run out of numbers: which is _probably_ a sign something has gone wrong");
-                        break;
-                    }
+    }
+}
+
+/// Iterator over every invalid number in some input, see [`invalid_numbers`]
+pub struct InvalidNumbers<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    index: usize,
+    window: Window,
+    failed: bool,
+}
+
+/// Reads one number per line and yields every number that isn't the sum of two of the numbers before it,
+/// only the last preamble_length numbers are kept, so the input can be much bigger than memory
+/// Blank lines are skipped, and the iterator stops after the first error
+pub fn invalid_numbers<R: BufRead>(reader: R, preamble_length: usize) -> InvalidNumbers<R> {
+    InvalidNumbers {
+        lines: reader.lines(),
+        line: 0,
+        index: 0,
+        window: Window::new(preamble_length),
+        failed: false,
+    }
+}
+
+impl<R: BufRead> Iterator for InvalidNumbers<R> {
+    type Item = Result<InvalidNumber, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        for line in &mut self.lines {
+            self.line += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(ScanError::Io(error)));
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let number = match line.parse() {
+                Ok(number) => number,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(ScanError::InvalidNumber {
+                        line: self.line,
+                        error,
+                    }));
                 }
+            };
+
+            let index = self.index;
+            self.index += 1;
+            let invalid = self.window.is_full() && self.window.validate(number).is_err();
+            self.window.push(number);
+            if invalid {
+                return Some(Ok(InvalidNumber { index, number }));
             }
-            println!("Did not crack. I have failed.");
-            None
-        } else {
-            println!("Could not crack because I have no weakness.");
-            None
         }
+        None
     }
 }
 
-#[aoc(day9, part1)]
-pub fn part1(input: &str) -> u64 {
-    // create an xmas with a preamble of length 25
-    let mut xmas = Xmas::new(25);
-    part1_inner(input, &mut xmas).expect("must detect weakness")
+/// Finds the first contiguous range of at least two numbers that add up to the target, in linear time
+///
+/// None of the numbers are negative, so moving the end of the range along can only make the sum bigger
+/// and moving the start along can only make it smaller.
+/// The range can include the target itself, but only if the rest of the range adds up to nothing.
+fn contiguous_sum(numbers: &[u64], target: u64) -> Option<EncryptionWeakness> {
+    // the numbers can each be up to u64::MAX, so a u128 sum can't overflow for any slice that fits in memory
+    let target = target as u128;
+    let mut start = 0;
+    let mut sum = 0u128;
+    for (end, &number) in numbers.iter().enumerate() {
+        sum += number as u128;
+        while sum > target {
+            sum -= numbers[start] as u128;
+            start += 1;
+        }
+        // start is now as far back as it can be, so if any range ending here works this one does
+        if sum == target && end > start {
+            let range = start..end + 1;
+            let smallest = numbers[range.clone()].iter().min()?;
+            let largest = numbers[range.clone()].iter().max()?;
+            return Some(EncryptionWeakness {
+                range,
+                value: smallest.checked_add(*largest),
+            });
+        }
+    }
+    None
 }
 
-pub fn part1_inner(input: &str, xmas: &mut Xmas) -> Option<u64> {
-    let ret = None;
-    for line in input.lines() {
-        let number = line
-            .parse::<u64>()
-            .expect("input must be positive integers");
-        if let Some(weakness) = xmas.process(number) {
-            return Some(weakness);
-        };
+/// The numbers to scan, and the preamble length to scan them with
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmasInput {
+    pub preamble_length: usize,
+    pub numbers: Vec<u64>,
+}
+
+impl XmasInput {
+    /// the first number that isn't the sum of two of the preamble_length numbers before it
+    pub fn find_weakness(&self) -> Result<Xmas<Weakness>, XmasError> {
+        let mut numbers = self.numbers.iter().copied();
+        Xmas::new(self.preamble_length)
+            .preamble(&mut numbers)?
+            .find_weakness(numbers)
     }
+}
 
-    ret
+/// One positive integer per line, after an optional header setting the preamble length
+#[aoc_generator(day9)]
+pub fn input_generator(input: &str) -> Result<XmasInput, XmasError> {
+    let (config, body) = split_header(input)?;
+    let preamble_length = config.get("preamble")?.unwrap_or(PREAMBLE_LENGTH);
+    let numbers = body
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            line.trim()
+                .parse()
+                .map_err(|error| XmasError::InvalidNumber {
+                    line: config.lines + index + 1,
+                    error,
+                })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(XmasInput {
+        preamble_length,
+        numbers,
+    })
 }
 
-#[aoc(day9, part2)]
-pub fn part2(input: &str) -> Option<u64> {
-    let mut xmas = Xmas::new(25);
-    part2_inner(input, &mut xmas)
+#[aoc(day9, part1)]
+pub fn part1(input: &XmasInput) -> Result<u64, XmasError> {
+    Ok(input.find_weakness()?.weakness())
 }
 
-pub fn part2_inner(input: &str, mut xmas: &mut Xmas) -> Option<u64> {
-    part1_inner(input, &mut xmas).expect("must detect weakness");
-    xmas.crack()
+#[aoc(day9, part2)]
+pub fn part2(input: &XmasInput) -> Result<u64, XmasError> {
+    let weakness = input.find_weakness()?.crack()?;
+    weakness
+        .value
+        .ok_or(XmasError::EncryptionWeaknessOverflow(weakness.range))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const TEST_INPUT: &'static str = "\
+    use crate::test_util::Lcg;
+
+    const TEST_INPUT: &str = "\
 35
 20
 15
@@ -178,21 +456,274 @@ mod test {
 309
 576";
 
+    /// our test case needs a different preamble length, which it sets in a header
+    fn example() -> XmasInput {
+        input_generator(&format!("# preamble = 5\n{}", TEST_INPUT)).unwrap()
+    }
+
+    #[test]
+    fn input_generator_works() {
+        let input = example();
+        assert_eq!(5, input.preamble_length);
+        assert_eq!(20, input.numbers.len());
+        assert_eq!(
+            PREAMBLE_LENGTH,
+            input_generator(TEST_INPUT).unwrap().preamble_length
+        );
+    }
+
     #[test]
     fn part1_works() {
-        // our test case needs a different preamble length, hence using part1_inner to share the functionality
         assert_eq!(
             127,
-            part1_inner(TEST_INPUT, &mut Xmas::new(5)).expect("failed to find known weakness!")
+            part1(&example()).expect("failed to find known weakness!")
         );
     }
 
     #[test]
     fn part2_works() {
-        // our test case needs a different preamble length, hence using part1_inner to share the functionality
         assert_eq!(
             62,
-            part2_inner(TEST_INPUT, &mut Xmas::new(5)).expect("failed to find known weakness!")
+            part2(&example()).expect("failed to find known weakness!")
+        );
+    }
+
+    #[test]
+    fn crack_finds_range() {
+        let xmas = example().find_weakness().unwrap();
+        assert_eq!(
+            Ok(EncryptionWeakness {
+                range: 2..6,
+                value: Some(62)
+            }),
+            xmas.crack()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let input = |input| input_generator(input).unwrap();
+        assert_eq!(
+            Err(XmasError::ShortPreamble {
+                expected: 5,
+                found: 3
+            }),
+            part1(&input("#preamble=5\n1\n2\n3"))
+        );
+        assert_eq!(
+            Err(XmasError::NoWeakness),
+            part1(&input("#preamble=2\n1\n2\n3\n5"))
+        );
+        assert_eq!(
+            Err(XmasError::NoEncryptionWeakness(10)),
+            part2(&input("#preamble=2\n1\n2\n3\n10"))
+        );
+        // line numbers count the header
+        assert!(matches!(
+            input_generator("#preamble=2\n1\n2\nthree"),
+            Err(XmasError::InvalidNumber { line: 4, .. })
+        ));
+        assert!(matches!(
+            input_generator("#preamble=two\n1\n2"),
+            Err(XmasError::Config(ConfigError::InvalidValue { line: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn contiguous_sum_works() {
+        // the weakness on its own isn't a range
+        assert_eq!(None, contiguous_sum(&[1, 2, 3, 10], 10));
+        // but it can be part of one
+        assert_eq!(
+            Some(EncryptionWeakness {
+                range: 3..5,
+                value: Some(10)
+            }),
+            contiguous_sum(&[1, 2, 3, 10, 0], 10)
+        );
+        // the first range found is the one that finishes first
+        assert_eq!(
+            Some(EncryptionWeakness {
+                range: 1..4,
+                value: Some(6)
+            }),
+            contiguous_sum(&[9, 4, 3, 2, 1, 6, 4], 9)
+        );
+        assert_eq!(
+            Some(EncryptionWeakness {
+                range: 0..2,
+                value: Some(4)
+            }),
+            contiguous_sum(&[4, 0, 0, 4], 4)
+        );
+        assert_eq!(None, contiguous_sum(&[], 0));
+        assert_eq!(None, contiguous_sum(&[5, 1, 5], 3));
+    }
+
+    #[test]
+    fn contiguous_sum_near_max() {
+        const MAX: u64 = u64::MAX;
+        assert_eq!(None, contiguous_sum(&[1, 2, MAX], MAX));
+        assert_eq!(
+            Some(EncryptionWeakness {
+                range: 1..3,
+                value: Some(MAX)
+            }),
+            contiguous_sum(&[MAX, MAX - 1, 1], MAX)
+        );
+        assert_eq!(
+            Some(EncryptionWeakness {
+                range: 2..4,
+                value: Some(MAX)
+            }),
+            contiguous_sum(&[MAX, MAX, MAX / 2, MAX / 2 + 1, MAX], MAX)
         );
+
+        let input = input_generator("# preamble = 2\n1\n2\n18446744073709551615").unwrap();
+        assert_eq!(Ok(MAX), part1(&input));
+        assert_eq!(Err(XmasError::NoEncryptionWeakness(MAX)), part2(&input));
+    }
+
+    #[test]
+    fn invalid_numbers_works() {
+        let input = TEST_INPUT.to_string() + "\n\n1\n1000\n";
+        let invalid: Vec<_> = invalid_numbers(input.as_bytes(), 5)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            vec![
+                InvalidNumber {
+                    index: 14,
+                    number: 127
+                },
+                InvalidNumber {
+                    index: 20,
+                    number: 1
+                },
+                InvalidNumber {
+                    index: 21,
+                    number: 1000
+                },
+            ],
+            invalid
+        );
+
+        let mut invalid = invalid_numbers("1\n2\n4\nfive\n6".as_bytes(), 2);
+        assert_eq!(
+            Some(InvalidNumber {
+                index: 2,
+                number: 4
+            }),
+            invalid.next().map(Result::unwrap)
+        );
+        assert!(matches!(
+            invalid.next(),
+            Some(Err(ScanError::InvalidNumber { line: 4, .. }))
+        ));
+        assert!(invalid.next().is_none());
+    }
+
+    /// the straightforward check: does any pair of different numbers in the preamble add up to the target
+    fn naive_valid(preamble: &[u64], target: u64) -> bool {
+        preamble.iter().enumerate().any(|(i, &a)| {
+            preamble[i + 1..]
+                .iter()
+                .any(|&b| a != b && a.checked_add(b) == Some(target))
+        })
+    }
+
+    /// a deterministic stream of numbers where nearly all are the sum of a recent pair
+    fn stream(length: usize, preamble_length: usize) -> Vec<u64> {
+        let mut lcg = Lcg::new(0x2020_0909);
+        let mut next = || lcg.random();
+        let mut numbers: Vec<u64> = (0..preamble_length).map(|_| next() % 1000).collect();
+        while numbers.len() < length {
+            let window = &numbers[numbers.len() - preamble_length..];
+            let a = window[next() as usize % preamble_length];
+            let b = window[next() as usize % preamble_length];
+            // sums keep growing, so start again from small numbers every so often
+            let number = if next() % 1000 == 0 || a + b > 1_000_000_000 {
+                next() % 1000
+            } else {
+                a + b
+            };
+            numbers.push(number);
+        }
+        numbers
+    }
+
+    #[test]
+    fn windowed_counts_match_naive() {
+        for &preamble_length in &[2, 3, 5, 25] {
+            let numbers = stream(2000, preamble_length);
+            // keeps going past any weakness, which process wouldn't
+            let mut xmas = Xmas::new(preamble_length)
+                .preamble(numbers.iter().copied())
+                .unwrap();
+            for (i, &number) in numbers.iter().enumerate().skip(preamble_length) {
+                assert_eq!(
+                    naive_valid(&numbers[i - preamble_length..i], number),
+                    xmas.validate(number).is_ok(),
+                    "number {} at {} with preamble {}",
+                    number,
+                    i,
+                    preamble_length
+                );
+                xmas.push(number);
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_numbers_are_not_a_pair() {
+        let xmas = Xmas::new(2).preamble(vec![3, 3]).unwrap();
+        assert_eq!(6, xmas.find_weakness(vec![6]).unwrap().weakness());
+
+        // a repeat leaving the window doesn't forget the other copy
+        let xmas = Xmas::new(3).preamble(vec![1, 2, 1]).unwrap();
+        assert_eq!(Err(XmasError::NoWeakness), xmas.find_weakness(vec![3, 4]));
+    }
+
+    #[test]
+    #[ignore]
+    fn validate_large_streams() {
+        use std::time::Instant;
+
+        for &preamble_length in &[25, 100, 1000] {
+            let numbers = stream(1_000_000, preamble_length);
+
+            let start = Instant::now();
+            let mut xmas = Xmas::new(preamble_length)
+                .preamble(numbers.iter().copied())
+                .unwrap();
+            let mut invalid = 0;
+            for &number in &numbers[preamble_length..] {
+                if xmas.validate(number).is_err() {
+                    invalid += 1;
+                }
+                xmas.push(number);
+            }
+            println!(
+                "preamble {}: window counts found {} invalid in {:?}",
+                preamble_length,
+                invalid,
+                start.elapsed()
+            );
+
+            if preamble_length > 100 {
+                // the naive scan takes far too long to be worth waiting for
+                continue;
+            }
+            let start = Instant::now();
+            let invalid = (preamble_length..numbers.len())
+                .filter(|&i| !naive_valid(&numbers[i - preamble_length..i], numbers[i]))
+                .count();
+            println!(
+                "preamble {}: naive scan found {} invalid in {:?}",
+                preamble_length,
+                invalid,
+                start.elapsed()
+            );
+        }
     }
 }
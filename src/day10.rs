@@ -1,76 +1,412 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::num::{NonZeroU64, ParseIntError};
+use std::ops::Range;
 
+use crate::bigint::BigUint;
+use crate::config::{split_header, ConfigError};
+
+const MIN_JUMP: u64 = 1;
 const MAX_JUMP: u64 = 3;
+const DEVICE_OFFSET: u64 = 3;
 
-/// generate a sorted list from the input
-#[aoc_generator(day10)]
-pub fn input_generator(input: &str) -> Vec<u64> {
-    let mut list = input
-        .lines()
-        .map(|n| n.parse::<u64>().expect("input must be positive integers"))
-        .collect::<Vec<_>>();
+/// Which joltages can be plugged into each other
+/// An adapter (or the device) can take an input between min_jump and max_jump jolts lower than its own joltage,
+/// and the device's joltage is device_offset higher than the highest adapter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JumpRules {
+    pub min_jump: u64,
+    pub max_jump: u64,
+    pub device_offset: u64,
+}
 
-    list.sort_unstable();
-    list
+impl Default for JumpRules {
+    fn default() -> Self {
+        JumpRules {
+            min_jump: MIN_JUMP,
+            max_jump: MAX_JUMP,
+            device_offset: DEVICE_OFFSET,
+        }
+    }
 }
 
-#[aoc(day10, part1)]
-pub fn part1(list: &Vec<u64>) -> usize {
-    // I think this is how it gets sorted by default!
-    assert!(list.first() < list.last());
+impl JumpRules {
+    pub fn allows(&self, jump: u64) -> bool {
+        self.min_jump <= jump && jump <= self.max_jump
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainError {
+    /// the jump from one joltage to the next is more than the max_jump
+    Gap { from: u64, to: u64 },
+    /// the jump from one joltage to the next is less than the min_jump
+    TooClose { from: u64, to: u64 },
+}
 
-    let mut current_voltage = 0;
-    let mut jump_counts = HashMap::<u64, usize>::new();
+impl std::error::Error for ChainError {}
 
-    for n in list {
-        match n - current_voltage {
-			jump if jump <= MAX_JUMP => {
-				// record the jump
-				*jump_counts.entry(jump).or_insert(0) += 1;
-				// increment the current_voltage for next time
-				current_voltage = *n;
-			},
-			_ => panic!("could not connect next adaptor as the difference in voltage was too high!\nFrom: {}\nTo: {}", current_voltage, n),
-		}
+impl std::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChainError::Gap { from, to } => {
+                write!(
+                    f,
+                    "can't connect {} jolts to {} jolts, the gap is too big",
+                    from, to
+                )
+            }
+            ChainError::TooClose { from, to } => {
+                write!(
+                    f,
+                    "can't connect {} jolts to {} jolts, they are too close",
+                    from, to
+                )
+            }
+        }
     }
+}
 
-    // add the final jump of 3 to the device's built in adaptor
-    jump_counts.get(&1).expect("must have a 1") * (jump_counts.get(&3).expect("must have a 3") + 1)
+/// One adapter from the bag
+/// Adapters are told apart by their position in the bag (starting at 0) as well as their joltage,
+/// so that two adapters with the same joltage are still different adapters
+/// They sort by joltage first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Adapter {
+    pub joltage: u64,
+    pub id: usize,
 }
 
-#[aoc(day10, part2)]
-pub fn part2(list: &Vec<u64>) -> u64 {
-    let mut route_solver = RouteSolver::new(list);
-    route_solver.solve()
+/// All of the adapters in the bag, sorted by joltage, and the rules for connecting them
+/// The chain starts at the charging outlet (0 jolts) and ends at the device
+///
+/// Adapters with the same joltage count as different adapters, so each one makes different arrangements
+/// Unless min_jump is 0 they can't be plugged into each other, so they can't all be used at once
+/// If min_jump is 0, adapters with the same joltage are only ever plugged into each other in the order they
+/// are in the bag, so swapping them around doesn't count as a different arrangement
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterChain {
+    adapters: Vec<Adapter>,
+    rules: JumpRules,
 }
 
-/// counts valid jumps from a position in the list
-pub fn count_jumps(index: usize, list: &Vec<u64>) -> u64 {
-    let mut jump = 0;
+impl AdapterChain {
+    /// the adapters' joltages in the order they are in the bag
+    pub fn new(joltages: impl IntoIterator<Item = u64>, rules: JumpRules) -> Self {
+        let mut adapters = joltages
+            .into_iter()
+            .enumerate()
+            .map(|(id, joltage)| Adapter { joltage, id })
+            .collect::<Vec<_>>();
+        adapters.sort_unstable();
+        AdapterChain { adapters, rules }
+    }
+
+    pub fn adapters(&self) -> &[Adapter] {
+        &self.adapters
+    }
+
+    pub fn rules(&self) -> &JumpRules {
+        &self.rules
+    }
+
+    /// the solver for counting routes through these adapters
+    pub fn route_solver(&self) -> RouteSolver {
+        let joltages = self
+            .adapters
+            .iter()
+            .map(|adapter| adapter.joltage)
+            .collect::<Vec<_>>();
+        RouteSolver::new(&joltages, &self.rules)
+    }
+
+    /// the joltage of the device's built-in adapter
+    pub fn device(&self) -> u64 {
+        self.adapters.last().map_or(0, |adapter| adapter.joltage) + self.rules.device_offset
+    }
 
-    for i in index + 1..=index + MAX_JUMP as usize {
-        if let Some(x) = list.get(i) {
-            if x - list[index] <= MAX_JUMP {
-                jump += 1;
+    /// every joltage in the chain that uses all of the adapters, from the outlet to the device
+    fn joltages(&self) -> impl Iterator<Item = u64> + '_ {
+        std::iter::once(0)
+            .chain(self.adapters.iter().map(|adapter| adapter.joltage))
+            .chain(std::iter::once(self.device()))
+    }
+
+    /// the adapters at these nodes of the route solver, which are the adapters' positions after the outlet
+    fn route_adapters(&self, route: &[usize]) -> Vec<Adapter> {
+        route.iter().map(|&node| self.adapters[node - 1]).collect()
+    }
+
+    /// checks that every adapter can be used at once, returning the first place the chain breaks if not
+    pub fn validate(&self) -> Result<(), ChainError> {
+        self.jumps().map(|_| ())
+    }
+
+    /// how many times each size of jump is made when using every adapter at once
+    pub fn jumps(&self) -> Result<BTreeMap<u64, usize>, ChainError> {
+        let mut histogram = BTreeMap::new();
+        let joltages = self.joltages().collect::<Vec<_>>();
+        for pair in joltages.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            match to - from {
+                jump if jump > self.rules.max_jump => return Err(ChainError::Gap { from, to }),
+                jump if jump < self.rules.min_jump => {
+                    return Err(ChainError::TooClose { from, to })
+                }
+                jump => *histogram.entry(jump).or_insert(0) += 1,
             }
+        }
+        Ok(histogram)
+    }
+
+    /// the number of distinct ways the adapters can connect the outlet to the device,
+    /// or None if there are too many to fit in a u64
+    pub fn arrangements(&self) -> Option<u64> {
+        self.route_solver().solve()
+    }
+
+    /// the number of distinct ways the adapters can connect the outlet to the device, however many there are
+    /// Only counts with big integers if there are too many arrangements to count with u64s, as that is much faster
+    pub fn arrangements_big(&self) -> BigUint {
+        let route_solver = self.route_solver();
+        match route_solver.solve() {
+            Some(arrangements) => BigUint::from(arrangements),
+            None => {
+                let routes = route_solver
+                    .fill_routes(
+                        BigUint::zero(),
+                        BigUint::from(1),
+                        |a, b| Some(a + b),
+                        |_, _| {},
+                    )
+                    .expect("big integers can't overflow");
+                routes[0].clone()
+            }
+        }
+    }
+
+    /// the number of distinct ways the adapters can connect the outlet to the device, modulo some number,
+    /// for when the exact number is too big to be useful
+    pub fn arrangements_modulo(&self, modulus: NonZeroU64) -> u64 {
+        let modulus = modulus.get();
+        let routes = self
+            .route_solver()
+            .fill_routes(
+                0,
+                1 % modulus,
+                |&a, &b| {
+                    // a and b are both less than modulus, but adding them can still overflow
+                    Some(((a as u128 + b as u128) % modulus as u128) as u64)
+                },
+                |_, _| {},
+            )
+            .expect("modular arithmetic can't overflow");
+        routes[0]
+    }
+
+    /// every arrangement, as the adapters it uses in order, lazily in lexicographic order
+    pub fn iter_arrangements(&self) -> ArrangementIter<'_> {
+        ArrangementIter::new(self.route_solver(), &self.adapters)
+    }
+
+    /// the arrangement at this rank (starting at 0) in lexicographic order,
+    /// or None if there aren't that many arrangements
+    pub fn nth_arrangement(&self, rank: u64) -> Option<Vec<Adapter>> {
+        let route_solver = self.route_solver();
+        let routes = route_solver.capped_routes();
+        let route = route_solver.nth_route(&routes, rank)?;
+        Some(self.route_adapters(&route))
+    }
+
+    /// picks one of the arrangements uniformly at random
+    /// random_below(n) must return a uniformly random number less than n
+    /// Returns None if there are no arrangements, or too many to number with a u64
+    pub fn sample_arrangement(
+        &self,
+        random_below: impl FnOnce(u64) -> u64,
+    ) -> Option<Vec<Adapter>> {
+        let route_solver = self.route_solver();
+        let routes = route_solver.capped_routes();
+        let route = match routes[0]? {
+            0 => return None,
+            total => route_solver.nth_route(&routes, random_below(total))?,
+        };
+        Some(self.route_adapters(&route))
+    }
+}
+
+/// Iterator over every arrangement of a chain, see [`AdapterChain::iter_arrangements`]
+pub struct ArrangementIter<'a> {
+    adapters: &'a [Adapter],
+    route_solver: RouteSolver,
+    routes: Vec<Option<u64>>,
+    /// the nodes on the current route from the outlet, with the next option to try from each of them
+    /// option 0 is plugging into the device, and option n is jumping to the nth node after it
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a> ArrangementIter<'a> {
+    fn new(route_solver: RouteSolver, adapters: &'a [Adapter]) -> Self {
+        let routes = route_solver.capped_routes();
+        let stack = if routes[0] == Some(0) {
+            Vec::new()
         } else {
-            break;
+            vec![(0, 0)]
+        };
+        ArrangementIter {
+            adapters,
+            route_solver,
+            routes,
+            stack,
+        }
+    }
+}
+
+impl<'a> Iterator for ArrangementIter<'a> {
+    type Item = Vec<Adapter>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let solver = &self.route_solver;
+        loop {
+            let (node, option) = self.stack.last_mut()?;
+            let node = *node;
+            let jumps = &solver.node_jumps[node];
+            if *option > jumps.len() {
+                self.stack.pop();
+                continue;
+            }
+            let current = *option;
+            *option += 1;
+
+            if current == 0 {
+                if solver.reaches_device[node] {
+                    return Some(
+                        self.stack[1..]
+                            .iter()
+                            .map(|&(node, _)| self.adapters[node - 1])
+                            .collect(),
+                    );
+                }
+            } else {
+                let target = jumps.start + current - 1;
+                // don't go down routes that never reach the device
+                if self.routes[target] != Some(0) {
+                    self.stack.push((target, 0));
+                }
+            }
         }
     }
-    jump
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterParseError {
+    Config(ConfigError),
+    /// a line (starting at 1) that isn't a positive integer
+    InvalidJoltage {
+        line: usize,
+        error: ParseIntError,
+    },
+}
+
+impl std::error::Error for AdapterParseError {}
+
+impl std::fmt::Display for AdapterParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AdapterParseError::Config(error) => error.fmt(f),
+            AdapterParseError::InvalidJoltage { line, error } => {
+                write!(f, "invalid joltage on line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl From<ConfigError> for AdapterParseError {
+    fn from(error: ConfigError) -> Self {
+        AdapterParseError::Config(error)
+    }
+}
+
+/// generate a sorted chain from the input
+/// An optional header can change the jump rules, e.g. `# max_jump = 4`
+#[aoc_generator(day10)]
+pub fn input_generator(input: &str) -> Result<AdapterChain, AdapterParseError> {
+    let (config, body) = split_header(input)?;
+    let defaults = JumpRules::default();
+    let rules = JumpRules {
+        min_jump: config.get("min_jump")?.unwrap_or(defaults.min_jump),
+        max_jump: config.get("max_jump")?.unwrap_or(defaults.max_jump),
+        device_offset: config
+            .get("device_offset")?
+            .unwrap_or(defaults.device_offset),
+    };
+    let adapters = body
+        .lines()
+        .enumerate()
+        .map(|(index, n)| {
+            n.trim()
+                .parse::<u64>()
+                .map_err(|error| AdapterParseError::InvalidJoltage {
+                    line: config.lines + index + 1,
+                    error,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(AdapterChain::new(adapters, rules))
+}
+
+/// the number of 1-jolt jumps multiplied by the number of 3-jolt jumps, using every adapter
+#[aoc(day10, part1)]
+pub fn part1(chain: &AdapterChain) -> Result<usize, ChainError> {
+    let jump_counts = chain.jumps()?;
+    Ok(jump_counts.get(&1).unwrap_or(&0) * jump_counts.get(&3).unwrap_or(&0))
+}
+
+#[aoc(day10, part2)]
+pub fn part2(chain: &AdapterChain) -> BigUint {
+    chain.arrangements_big()
+}
+
+/// the positions in the list that can be jumped to from a position in the list
+/// The list is sorted, so these are always next to each other
+pub fn jump_targets(index: usize, list: &[u64], rules: &JumpRules) -> Range<usize> {
+    let from = list[index];
+    let mut start = index + 1;
+    while start < list.len() && list[start] - from < rules.min_jump {
+        start += 1;
+    }
+    let mut end = start;
+    while end < list.len() && list[end] - from <= rules.max_jump {
+        end += 1;
+    }
+    start..end
+}
+
+/// Counts the routes from the outlet to the device, by counting the routes from every adapter
+/// in turn, starting with the highest
+/// node_jumps are the nodes (positions in list) each node can jump to,
+/// and reaches_device is whether each node can plug straight into the device
+#[derive(Debug, Clone, PartialEq)]
 pub struct RouteSolver {
     list: Vec<u64>,
-    node_jumps: Vec<u64>,
-    node_routes: Vec<u64>,
+    node_jumps: Vec<Range<usize>>,
+    reaches_device: Vec<bool>,
+}
+
+/// The number of routes to the device from one node, reported as the routes are counted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteStep {
+    /// the position in the list, where 0 is the outlet
+    pub node: usize,
+    pub joltage: u64,
+    pub routes: u64,
 }
 
 impl RouteSolver {
-    fn new(input: &Vec<u64>) -> Self {
+    /// The joltages must be sorted, which AdapterChain::route_solver guarantees
+    pub(crate) fn new(input: &[u64], rules: &JumpRules) -> Self {
         let list = std::iter::once(0)
             .chain(input.iter().cloned())
             .collect::<Vec<_>>();
@@ -78,31 +414,112 @@ impl RouteSolver {
         let node_jumps = list
             .iter()
             .enumerate()
-            .map(|(i, _)| count_jumps(i, &list))
+            .map(|(i, _)| jump_targets(i, &list, rules))
+            .collect();
+        // there is exactly one route from any adapter that can plug straight into the device
+        let device = input.last().copied().unwrap_or(0) + rules.device_offset;
+        let reaches_device = list
+            .iter()
+            .map(|&joltage| rules.allows(device - joltage))
             .collect();
-        let mut node_routes = std::iter::repeat(0).take(list.len()).collect::<Vec<u64>>();
-        node_routes[list.len() - 1] = 1;
 
         RouteSolver {
             list,
             node_jumps,
-            node_routes,
+            reaches_device,
         }
     }
 
-    fn solve(&mut self) -> u64 {
-        dbg!(&self.list);
+    /// the number of routes from the outlet to the device, or None if there are too many to fit in a u64
+    pub fn solve(&self) -> Option<u64> {
+        self.solve_with(|_| {})
+    }
+
+    /// solves, calling observe with the number of routes from each node as soon as it has been counted
+    pub fn solve_with(&self, mut observe: impl FnMut(RouteStep)) -> Option<u64> {
+        let routes = self.fill_routes(
+            0u64,
+            1,
+            |a, b| a.checked_add(*b),
+            |node, &routes| {
+                observe(RouteStep {
+                    node,
+                    joltage: self.list[node],
+                    routes,
+                })
+            },
+        )?;
+        Some(routes[0])
+    }
+
+    /// counts the routes to the device from every node, where None means there are too many to fit in a u64
+    fn capped_routes(&self) -> Vec<Option<u64>> {
+        self.fill_routes(
+            Some(0u64),
+            Some(1),
+            |a, b| Some(a.zip(*b).and_then(|(a, b)| a.checked_add(b))),
+            |_, _| {},
+        )
+        .expect("capped counts can't overflow")
+    }
+
+    /// the route to the device at this rank in lexicographic order, as the nodes it goes through after the outlet
+    /// Plugging into the device comes before jumping to any other adapter, as a shorter route sorts first,
+    /// and then the routes through each adapter in turn, skipping over as many as the rank allows
+    fn nth_route(&self, routes: &[Option<u64>], mut rank: u64) -> Option<Vec<usize>> {
+        if routes[0].is_some_and(|total| rank >= total) {
+            return None;
+        }
+        let mut route = Vec::new();
+        let mut node = 0;
+        loop {
+            if self.reaches_device[node] {
+                if rank == 0 {
+                    return Some(route);
+                }
+                rank -= 1;
+            }
+            let mut next = None;
+            for target in self.node_jumps[node].clone() {
+                match routes[target] {
+                    Some(count) if rank >= count => rank -= count,
+                    _ => {
+                        next = Some(target);
+                        break;
+                    }
+                }
+            }
+            // the rank is always less than the number of routes from node, so one of them must be next
+            node = next?;
+            route.push(node);
+        }
+    }
+
+    /// counts the routes to the device from every node, working backwards from the device,
+    /// using whichever type of number zero and one are
+    /// add returns None if the sum doesn't fit in that type, in which case so does this
+    /// observe is called with each node and its count as soon as it has been counted
+    fn fill_routes<C: Clone>(
+        &self,
+        zero: C,
+        one: C,
+        add: impl Fn(&C, &C) -> Option<C>,
+        mut observe: impl FnMut(usize, &C),
+    ) -> Option<Vec<C>> {
+        let mut routes = vec![zero.clone(); self.list.len()];
         for i in (0..self.list.len()).rev() {
-            let mut checks = self.node_jumps[i] as usize;
-            dbg!(i, self.list[i], self.node_jumps[i], self.node_routes[i]);
-            while checks > 0 {
-                self.node_routes[i] += self.node_routes[i + checks];
-                dbg!(i, self.node_jumps[i], self.list[i], self.node_routes[i]);
-                checks -= 1;
+            let mut total = if self.reaches_device[i] {
+                one.clone()
+            } else {
+                zero.clone()
+            };
+            for target in self.node_jumps[i].clone() {
+                total = add(&total, &routes[target])?;
             }
+            observe(i, &total);
+            routes[i] = total;
         }
-        dbg!(&self);
-        self.node_routes[0]
+        Some(routes)
     }
 }
 
@@ -110,7 +527,11 @@ impl RouteSolver {
 mod test {
     use super::*;
 
-    const TEST_INPUT: &'static str = "\
+    use std::collections::HashSet;
+
+    use crate::test_util::Lcg;
+
+    const TEST_INPUT: &str = "\
 28
 33
 18
@@ -143,7 +564,7 @@ mod test {
 10
 3";
 
-    const TEST_INPUT2: &'static str = "\
+    const TEST_INPUT2: &str = "\
 16
 10
 15
@@ -166,22 +587,31 @@ mod test {
 
     #[test]
     fn part1_works() {
-        assert_eq!(220, part1(&input_generator(TEST_INPUT)));
-        assert_eq!(35, part1(&input_generator(TEST_INPUT2)));
+        assert_eq!(220, part1(&input_generator(TEST_INPUT).unwrap()).unwrap());
+        assert_eq!(35, part1(&input_generator(TEST_INPUT2).unwrap()).unwrap());
     }
 
     #[test]
     fn route_solver_inits() {
         let long_list = long_list();
-        let route_solver = RouteSolver::new(&long_list);
+        let route_solver = RouteSolver::new(&long_list, &JumpRules::default());
 
         //								   vec![0, 1, 4, 5, 6, 7, 9, 10,11,12,14,16,17,19,22]
         assert_eq!(
-            route_solver.node_jumps,
+            route_solver
+                .node_jumps
+                .iter()
+                .map(|jumps| jumps.len())
+                .collect::<Vec<_>>(),
             vec![1, 1, 3, 2, 2, 2, 3, 2, 2, 1, 2, 2, 1, 1, 0]
         );
+        // only the highest adapter can plug into the device
         assert_eq!(
-            route_solver.node_routes,
+            route_solver
+                .reaches_device
+                .iter()
+                .map(|&reaches| if reaches { 1 } else { 0 })
+                .collect::<Vec<_>>(),
             vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
         );
     }
@@ -192,7 +622,7 @@ mod test {
         let mut list = vec![0, 1, 2];
         for x in 2..100 {
             list.push(x);
-            let mut route_solver = RouteSolver::new(&list);
+            let route_solver = RouteSolver::new(&list, &JumpRules::default());
             let out = route_solver.solve();
             println!("{}: {:#?}", x, out);
         }
@@ -201,14 +631,348 @@ mod test {
     #[test]
     fn route_solver_works() {
         let long_list = long_list();
-        let mut route_solver = RouteSolver::new(&long_list);
+        let route_solver = RouteSolver::new(&long_list, &JumpRules::default());
 
-        assert_eq!(144, route_solver.solve());
+        assert_eq!(Some(144), route_solver.solve());
+    }
+
+    #[test]
+    fn route_solver_solves_repeatedly() {
+        let route_solver = RouteSolver::new(&long_list(), &JumpRules::default());
+        let before = route_solver.clone();
+        for _ in 0..3 {
+            assert_eq!(Some(144), route_solver.solve());
+        }
+        assert_eq!(before, route_solver);
+
+        let chain = input_generator(TEST_INPUT).unwrap();
+        assert_eq!(chain.arrangements(), chain.arrangements());
+        assert_eq!(Some(19208), chain.arrangements());
+    }
+
+    #[test]
+    fn route_solver_traces() {
+        let route_solver = RouteSolver::new(&long_list(), &JumpRules::default());
+        let mut steps = Vec::new();
+        assert_eq!(Some(144), route_solver.solve_with(|step| steps.push(step)));
+
+        // every node is counted once, from the highest joltage down to the outlet
+        assert_eq!(15, steps.len());
+        assert!(steps
+            .windows(2)
+            .all(|pair| pair[0].node == pair[1].node + 1));
+        assert_eq!(
+            RouteStep {
+                node: 14,
+                joltage: 22,
+                routes: 1
+            },
+            steps[0]
+        );
+        assert_eq!(
+            RouteStep {
+                node: 0,
+                joltage: 0,
+                routes: 144
+            },
+            steps[14]
+        );
+
+        // the counts are the same each time
+        let mut again = Vec::new();
+        route_solver.solve_with(|step| again.push(step));
+        assert_eq!(steps, again);
     }
 
     #[test]
     fn part2_works() {
-        assert_eq!(8, part2(&input_generator(TEST_INPUT2)));
-        assert_eq!(19208, part2(&input_generator(TEST_INPUT)));
+        assert_eq!(
+            BigUint::from(8),
+            part2(&input_generator(TEST_INPUT2).unwrap())
+        );
+        assert_eq!(
+            BigUint::from(19208),
+            part2(&input_generator(TEST_INPUT).unwrap())
+        );
+    }
+
+    #[test]
+    fn adapter_chain_works() {
+        let chain = input_generator(TEST_INPUT2).unwrap();
+        assert_eq!(22, chain.device());
+        assert_eq!(Ok(()), chain.validate());
+        assert_eq!(
+            vec![(1, 7), (3, 5)],
+            chain.jumps().unwrap().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(8), chain.arrangements());
+    }
+
+    #[test]
+    fn adapter_chain_breaks() {
+        let chain = AdapterChain::new(vec![1, 2, 6, 7], JumpRules::default());
+        assert_eq!(Err(ChainError::Gap { from: 2, to: 6 }), chain.validate());
+        assert_eq!(Err(ChainError::Gap { from: 2, to: 6 }), part1(&chain));
+        assert_eq!(Some(0), chain.arrangements());
+
+        let rules = JumpRules {
+            min_jump: 2,
+            ..JumpRules::default()
+        };
+        let chain = AdapterChain::new(vec![2, 3, 5], rules);
+        assert_eq!(
+            Err(ChainError::TooClose { from: 2, to: 3 }),
+            chain.validate()
+        );
+        // 0 -> 2 -> 5 -> 8 and 0 -> 3 -> 5 -> 8
+        assert_eq!(Some(2), chain.arrangements());
+    }
+
+    #[test]
+    fn big_arrangements() {
+        // with every joltage from 1 to n, the arrangements are the tribonacci numbers
+        let tribonacci = |n: usize| {
+            let mut routes = vec![1u128, 1, 2];
+            while routes.len() <= n {
+                let len = routes.len();
+                routes.push(routes[len - 1] + routes[len - 2] + routes[len - 3]);
+            }
+            routes[n]
+        };
+
+        let chain = AdapterChain::new(1..=70, JumpRules::default());
+        assert_eq!(Some(tribonacci(70) as u64), chain.arrangements());
+        assert_eq!(
+            tribonacci(70).to_string(),
+            chain.arrangements_big().to_string()
+        );
+
+        let chain = AdapterChain::new(1..=120, JumpRules::default());
+        assert_eq!(None, chain.arrangements());
+        assert_eq!(
+            tribonacci(120).to_string(),
+            chain.arrangements_big().to_string()
+        );
+        for &modulus in &[1, 7, 1_000_000_007, u64::MAX] {
+            assert_eq!(
+                (tribonacci(120) % modulus as u128) as u64,
+                chain.arrangements_modulo(NonZeroU64::new(modulus).unwrap())
+            );
+        }
+    }
+
+    /// checks an arrangement only makes jumps that the rules allow
+    /// and that it only uses adapters from the chain, each at most once
+    fn valid_arrangement(chain: &AdapterChain, arrangement: &[Adapter]) -> bool {
+        let joltages = std::iter::once(0)
+            .chain(joltages(arrangement))
+            .chain(std::iter::once(chain.device()))
+            .collect::<Vec<_>>();
+        let ids = arrangement
+            .iter()
+            .map(|adapter| adapter.id)
+            .collect::<HashSet<_>>();
+        joltages
+            .windows(2)
+            .all(|pair| pair[0] <= pair[1] && chain.rules().allows(pair[1] - pair[0]))
+            && ids.len() == arrangement.len()
+            && arrangement
+                .iter()
+                .all(|adapter| chain.adapters().contains(adapter))
+    }
+
+    fn joltages(arrangement: &[Adapter]) -> Vec<u64> {
+        arrangement.iter().map(|adapter| adapter.joltage).collect()
+    }
+
+    #[test]
+    fn iter_arrangements_works() {
+        let chain = input_generator(TEST_INPUT2).unwrap();
+        let arrangements = chain.iter_arrangements().collect::<Vec<_>>();
+        assert_eq!(8, arrangements.len());
+        assert_eq!(
+            vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19],
+            joltages(&arrangements[0])
+        );
+        assert_eq!(
+            vec![1, 4, 7, 10, 12, 15, 16, 19],
+            joltages(&arrangements[7])
+        );
+
+        for chain in &[
+            input_generator(TEST_INPUT).unwrap(),
+            AdapterChain::new(long_list(), JumpRules::default()),
+            AdapterChain::new(
+                vec![2, 3, 5],
+                JumpRules {
+                    min_jump: 2,
+                    ..JumpRules::default()
+                },
+            ),
+            AdapterChain::new(vec![3, 1, 2, 1, 3, 4, 4], JumpRules::default()),
+            AdapterChain::new(
+                vec![2, 1, 1, 3],
+                JumpRules {
+                    min_jump: 0,
+                    ..JumpRules::default()
+                },
+            ),
+        ] {
+            let arrangements = chain.iter_arrangements().collect::<Vec<_>>();
+            assert_eq!(chain.arrangements(), Some(arrangements.len() as u64));
+            assert!(arrangements.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(arrangements.iter().all(|a| valid_arrangement(chain, a)));
+            for (rank, arrangement) in arrangements.iter().enumerate() {
+                assert_eq!(
+                    Some(arrangement),
+                    chain.nth_arrangement(rank as u64).as_ref()
+                );
+            }
+            assert_eq!(None, chain.nth_arrangement(arrangements.len() as u64));
+        }
+
+        let broken = AdapterChain::new(vec![1, 2, 6, 7], JumpRules::default());
+        assert_eq!(0, broken.iter_arrangements().count());
+        assert_eq!(None, broken.nth_arrangement(0));
+        assert_eq!(None, broken.sample_arrangement(|_| 0));
+    }
+
+    #[test]
+    fn nth_arrangement_of_huge_chains() {
+        // far too many arrangements to count in a u64, but they can still be ranked
+        let chain = AdapterChain::new(1..=200, JumpRules::default());
+        assert_eq!(None, chain.arrangements());
+        // using every adapter sorts first
+        assert_eq!(
+            Some((1..=200).collect::<Vec<_>>()),
+            chain
+                .nth_arrangement(0)
+                .map(|arrangement| joltages(&arrangement))
+        );
+        assert_eq!(chain.iter_arrangements().next(), chain.nth_arrangement(0));
+        let last = chain.nth_arrangement(u64::MAX).unwrap();
+        assert!(valid_arrangement(&chain, &last));
+        // but they can't be sampled from uniformly
+        assert_eq!(None, chain.sample_arrangement(|_| 0));
+    }
+
+    #[test]
+    fn sample_arrangement_works() {
+        let chain = input_generator(TEST_INPUT2).unwrap();
+        let arrangements = chain.iter_arrangements().collect::<Vec<_>>();
+
+        let mut lcg = Lcg::new(0x2020_1010);
+        let mut counts = vec![0; arrangements.len()];
+        for _ in 0..8000 {
+            let sample = chain.sample_arrangement(|n| lcg.random() % n).unwrap();
+            let index = arrangements.iter().position(|a| a == &sample).unwrap();
+            counts[index] += 1;
+        }
+        // each of the 8 arrangements should come up about 1000 times
+        assert!(
+            counts.iter().all(|&count| 850 < count && count < 1150),
+            "{:?}",
+            counts
+        );
+    }
+
+    /// counts arrangements by trying every set of adapters, which only works for small bags
+    /// Unless min_jump is 0, each set of adapters can only be plugged together in one order
+    fn brute_force_arrangements(chain: &AdapterChain) -> u64 {
+        let adapters = chain.adapters();
+        (0..1u32 << adapters.len())
+            .filter(|set| {
+                let arrangement = adapters
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| set & 1 << i != 0)
+                    .map(|(_, &adapter)| adapter)
+                    .collect::<Vec<_>>();
+                valid_arrangement(chain, &arrangement)
+            })
+            .count() as u64
+    }
+
+    #[test]
+    fn duplicate_adapters() {
+        let chain = input_generator("1\n1\n2").unwrap();
+        assert_eq!(
+            vec![
+                Adapter { joltage: 1, id: 0 },
+                Adapter { joltage: 1, id: 1 },
+                Adapter { joltage: 2, id: 2 }
+            ],
+            chain.adapters()
+        );
+        // the duplicates can't both be used, as they can't plug into each other
+        assert_eq!(Err(ChainError::TooClose { from: 1, to: 1 }), part1(&chain));
+        // but either of them can be used, so 0 -> 1 -> 2 happens twice, along with 0 -> 2
+        assert_eq!(Some(3), chain.arrangements());
+        let arrangements = chain.iter_arrangements().collect::<Vec<_>>();
+        assert_eq!(
+            vec![vec![1, 2], vec![1, 2], vec![2]],
+            arrangements
+                .iter()
+                .map(|arrangement| joltages(arrangement))
+                .collect::<Vec<_>>()
+        );
+        assert_ne!(arrangements[0], arrangements[1]);
+
+        for input in &[
+            "3\n1\n2\n1\n3\n4\n4",
+            "1\n1\n1\n1\n2\n4\n4\n5\n7\n7",
+            "3\n3\n3\n6\n6\n9",
+            "# min_jump = 0\n2\n1\n1\n3\n3",
+            "# max_jump = 4\n# device_offset = 1\n4\n4\n8\n9\n9\n10",
+        ] {
+            let chain = input_generator(input).unwrap();
+            assert_eq!(
+                Some(brute_force_arrangements(&chain)),
+                chain.arrangements(),
+                "{}",
+                input
+            );
+            assert_eq!(
+                chain.arrangements(),
+                Some(chain.iter_arrangements().count() as u64)
+            );
+        }
+
+        // with a min_jump of 0, every adapter can be used at once
+        let chain = input_generator("# min_jump = 0\n2\n1\n1\n3").unwrap();
+        assert_eq!(
+            vec![(0, 1), (1, 3), (3, 1)],
+            chain.jumps().unwrap().into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn configurable_rules() {
+        let chain = input_generator("# max_jump = 4\n# device_offset = 1\n4\n8\n10").unwrap();
+        assert_eq!(
+            &JumpRules {
+                min_jump: 1,
+                max_jump: 4,
+                device_offset: 1
+            },
+            chain.rules()
+        );
+        assert_eq!(11, chain.device());
+        assert_eq!(
+            vec![(1, 1), (2, 1), (4, 2)],
+            chain.jumps().unwrap().into_iter().collect::<Vec<_>>()
+        );
+        // 8 can go straight to the device, or through 10
+        assert_eq!(Some(2), chain.arrangements());
+
+        // with the default rules, the outlet can't reach the first adapter
+        assert_eq!(
+            Err(ChainError::Gap { from: 0, to: 4 }),
+            input_generator("4\n8\n10").unwrap().validate()
+        );
+        assert!(matches!(
+            input_generator("# max_jump = 4\n4\nfour"),
+            Err(AdapterParseError::InvalidJoltage { line: 3, .. })
+        ));
     }
 }
@@ -5,11 +5,11 @@ mod day02;
 mod day03;
 mod day04;
 mod day05;
-mod day06;
-mod day07;
-mod day08;
-mod day09;
-mod day10;
+pub mod day06;
+pub mod day07;
+pub mod day08;
+pub mod day09;
+pub mod day10;
 mod day11;
 mod day12;
 mod day13;
@@ -26,4 +26,11 @@ mod day16;
 // mod day24;
 // mod day25;
 
+pub mod bigint;
+pub mod config;
+pub mod console;
+mod records;
+#[cfg(test)]
+mod test_util;
+
 aoc_lib! { year = 2020 }
@@ -0,0 +1,78 @@
+//! Reader for inputs made of records separated by blank lines, e.g. day 4 passports or day 6 groups
+
+/// A run of non-blank lines from the input
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record<'a> {
+    /// the line number (starting at 1) of the first line of the record
+    pub line: usize,
+    pub lines: Vec<&'a str>,
+}
+
+/// Iterator over the records in some input, see [`records`]
+pub struct Records<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+}
+
+/// Splits input into records separated by one or more blank lines
+/// Handles both `\n` and `\r\n` line endings, a line containing only whitespace counts as blank
+/// Leading and trailing blank lines never produce empty records
+pub fn records(input: &str) -> Records<'_> {
+    Records {
+        lines: input.lines().enumerate(),
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record: Option<Record<'a>> = None;
+        for (index, line) in &mut self.lines {
+            // lines() only strips a '\r' that comes right before a '\n'
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                if record.is_some() {
+                    break;
+                }
+            } else {
+                record
+                    .get_or_insert_with(|| Record {
+                        line: index + 1,
+                        lines: Vec::new(),
+                    })
+                    .lines
+                    .push(line);
+            }
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_works() {
+        let input = "\n\nab\r\nc\r\n\r\n \r\n\nd\n\n\n";
+        assert_eq!(
+            vec![
+                Record {
+                    line: 3,
+                    lines: vec!["ab", "c"]
+                },
+                Record {
+                    line: 8,
+                    lines: vec!["d"]
+                },
+            ],
+            records(input).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn no_records() {
+        assert_eq!(0, records("").count());
+        assert_eq!(0, records("\n\r\n\n").count());
+    }
+}
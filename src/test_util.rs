@@ -0,0 +1,19 @@
+//! Helpers shared between the tests of different days
+
+/// A deterministic pseudo-random number generator, so generated test inputs are the same on every run
+/// A 64 bit linear congruential generator, returning the top 31 bits of its state
+pub(crate) struct Lcg(u64);
+
+impl Lcg {
+    pub(crate) fn new(seed: u64) -> Self {
+        Lcg(seed)
+    }
+
+    pub(crate) fn random(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+}